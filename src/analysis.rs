@@ -0,0 +1,450 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use wasmparser::{
+    for_each_operator, BlockType, ExternalKind, KnownCustom, Name, Operator, Parser, Payload,
+    TypeRef,
+};
+
+/// The size of a section of a WASM module
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionSize {
+    /// The section kind, or `custom:<name>` for custom sections
+    pub name: String,
+    /// The size of the section contents in bytes
+    pub size: u64,
+}
+
+/// The size of a function body of a WASM module
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionSize {
+    /// The function index, including imported functions
+    pub index: u32,
+    /// The name of the function from the name section, if there is one
+    pub name: Option<String>,
+    /// The size of the function body in bytes
+    pub size: u64,
+}
+
+impl FunctionSize {
+    /// Gets the name of the function, falling back to its index
+    ///
+    /// Example: `my_gauge_callback` or `func[42]`
+    pub fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("func[{}]", self.index))
+    }
+}
+
+/// An imported or exported item of a WASM module
+#[derive(Debug, Clone, Serialize)]
+pub struct ModuleItem {
+    /// The module the item is imported from, for imports
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    pub name: String,
+    /// `func`, `table`, `memory`, `global` or `tag`
+    pub kind: &'static str,
+}
+
+/// The limits of a memory or table, in pages or elements
+#[derive(Debug, Clone, Serialize)]
+pub struct Limits {
+    pub initial: u64,
+    pub maximum: Option<u64>,
+    /// Whether the memory is shared between threads
+    pub shared: bool,
+    /// The element type of a table
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub element_type: Option<String>,
+}
+
+/// A WASM proposal beyond the MVP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WasmFeature {
+    SignExt,
+    NontrappingFloatToInt,
+    BulkMemory,
+    MultiValue,
+    ReferenceTypes,
+    Simd,
+    Threads,
+    TailCall,
+    Exceptions,
+    Gc,
+    /// Any other proposal, e.g. memory control or wide arithmetic
+    Other,
+}
+
+impl WasmFeature {
+    /// Gets the name of the feature as used by wasm-opt and in JSON output
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SignExt => "sign-ext",
+            Self::NontrappingFloatToInt => "nontrapping-float-to-int",
+            Self::BulkMemory => "bulk-memory",
+            Self::MultiValue => "multi-value",
+            Self::ReferenceTypes => "reference-types",
+            Self::Simd => "simd",
+            Self::Threads => "threads",
+            Self::TailCall => "tail-call",
+            Self::Exceptions => "exceptions",
+            Self::Gc => "gc",
+            Self::Other => "other",
+        }
+    }
+
+    /// Gets the feature of an operator, or `None` for MVP operators
+    ///
+    /// * `operator` - The operator
+    fn of_operator(operator: &Operator) -> Option<Self> {
+        macro_rules! match_operator {
+            ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident ($($ann:tt)*))*) => {
+                match operator {
+                    // Blocks typed by a function type can take parameters or return multiple values
+                    Operator::Block { blockty: BlockType::FuncType(_) }
+                    | Operator::Loop { blockty: BlockType::FuncType(_) }
+                    | Operator::If { blockty: BlockType::FuncType(_) } => Some(Self::MultiValue),
+                    $( Operator::$op { .. } => match_operator!(feature @$proposal), )*
+                    _ => Some(Self::Other),
+                }
+            };
+            (feature @mvp) => { None };
+            (feature @sign_extension) => { Some(Self::SignExt) };
+            (feature @saturating_float_to_int) => { Some(Self::NontrappingFloatToInt) };
+            (feature @bulk_memory) => { Some(Self::BulkMemory) };
+            (feature @reference_types) => { Some(Self::ReferenceTypes) };
+            (feature @simd) => { Some(Self::Simd) };
+            (feature @relaxed_simd) => { Some(Self::Simd) };
+            (feature @threads) => { Some(Self::Threads) };
+            (feature @shared_everything_threads) => { Some(Self::Threads) };
+            (feature @tail_call) => { Some(Self::TailCall) };
+            (feature @exceptions) => { Some(Self::Exceptions) };
+            (feature @legacy_exceptions) => { Some(Self::Exceptions) };
+            (feature @gc) => { Some(Self::Gc) };
+            (feature @function_references) => { Some(Self::Gc) };
+            (feature @$proposal:ident) => { Some(Self::Other) };
+        }
+        for_each_operator!(match_operator)
+    }
+}
+
+/// The structure of a WASM module
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ModuleAnalysis {
+    /// The size of the whole module in bytes
+    pub size: u64,
+    /// Every section, in the order they appear in
+    pub sections: Vec<SectionSize>,
+    /// Every function defined by the module, in index order
+    #[serde(skip)]
+    pub functions: Vec<FunctionSize>,
+    pub imports: Vec<ModuleItem>,
+    pub exports: Vec<ModuleItem>,
+    /// Every memory, including imported ones
+    pub memories: Vec<Limits>,
+    /// Every table, including imported ones
+    pub tables: Vec<Limits>,
+    /// The features used by the module and the functions using them. Features used outside of code, like shared memories, have no functions
+    pub features: BTreeMap<WasmFeature, Vec<String>>,
+}
+
+impl ModuleAnalysis {
+    /// Gets the largest functions, biggest first
+    ///
+    /// * `count` - How many functions to return at most
+    pub fn largest_functions(&self, count: usize) -> Vec<FunctionSize> {
+        let mut functions = self.functions.clone();
+        functions.sort_by(|a, b| b.size.cmp(&a.size).then(a.index.cmp(&b.index)));
+        functions.truncate(count);
+        functions
+    }
+}
+
+/// Gets the display name of an external kind
+///
+/// * `kind` - The external kind
+fn kind_name(kind: ExternalKind) -> &'static str {
+    match kind {
+        ExternalKind::Func => "func",
+        ExternalKind::Table => "table",
+        ExternalKind::Memory => "memory",
+        ExternalKind::Global => "global",
+        ExternalKind::Tag => "tag",
+    }
+}
+
+/// Gets the display name of a section
+///
+/// * `id` - The section ID
+fn section_name(id: u8) -> &'static str {
+    match id {
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "datacount",
+        13 => "tag",
+        _ => "unknown",
+    }
+}
+
+/// Parses a WASM module into its sections, functions, imports, exports, memories, tables and used features
+///
+/// * `wasm` - The WASM module
+pub fn analyze_module(wasm: &[u8]) -> Result<ModuleAnalysis> {
+    let mut analysis = ModuleAnalysis {
+        size: wasm.len() as u64,
+        ..Default::default()
+    };
+    let mut imported_functions = 0;
+    let mut names = HashMap::new();
+    // Whether each function type returns multiple values
+    let mut multi_value_types = vec![];
+    let mut function_types = vec![];
+    // The features used by each defined function, in index order
+    let mut function_features = vec![];
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload?;
+        match &payload {
+            Payload::TypeSection(types) => {
+                for ty in types.clone().into_iter_err_on_gc_types() {
+                    multi_value_types.push(ty?.results().len() > 1);
+                }
+            }
+            Payload::ImportSection(imports) => {
+                for import in imports.clone() {
+                    let import = import?;
+                    let kind = match import.ty {
+                        TypeRef::Func(_) => {
+                            imported_functions += 1;
+                            ExternalKind::Func
+                        }
+                        TypeRef::Table(ty) => {
+                            analysis.tables.push(Limits {
+                                initial: ty.initial,
+                                maximum: ty.maximum,
+                                shared: ty.shared,
+                                element_type: Some(ty.element_type.to_string()),
+                            });
+                            ExternalKind::Table
+                        }
+                        TypeRef::Memory(ty) => {
+                            analysis.memories.push(Limits {
+                                initial: ty.initial,
+                                maximum: ty.maximum,
+                                shared: ty.shared,
+                                element_type: None,
+                            });
+                            ExternalKind::Memory
+                        }
+                        TypeRef::Global(_) => ExternalKind::Global,
+                        TypeRef::Tag(_) => ExternalKind::Tag,
+                    };
+                    analysis.imports.push(ModuleItem {
+                        module: Some(import.module.to_string()),
+                        name: import.name.to_string(),
+                        kind: kind_name(kind),
+                    });
+                }
+            }
+            Payload::FunctionSection(functions) => {
+                for ty in functions.clone() {
+                    function_types.push(ty?);
+                }
+            }
+            Payload::TableSection(tables) => {
+                for table in tables.clone() {
+                    let ty = table?.ty;
+                    analysis.tables.push(Limits {
+                        initial: ty.initial,
+                        maximum: ty.maximum,
+                        shared: ty.shared,
+                        element_type: Some(ty.element_type.to_string()),
+                    });
+                }
+            }
+            Payload::MemorySection(memories) => {
+                for ty in memories.clone() {
+                    let ty = ty?;
+                    analysis.memories.push(Limits {
+                        initial: ty.initial,
+                        maximum: ty.maximum,
+                        shared: ty.shared,
+                        element_type: None,
+                    });
+                }
+            }
+            Payload::ExportSection(exports) => {
+                for export in exports.clone() {
+                    let export = export?;
+                    analysis.exports.push(ModuleItem {
+                        module: None,
+                        name: export.name.to_string(),
+                        kind: kind_name(export.kind),
+                    });
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                let defined_index = analysis.functions.len();
+                analysis.functions.push(FunctionSize {
+                    index: imported_functions + defined_index as u32,
+                    name: None,
+                    size: body.range().len() as u64,
+                });
+
+                let mut features = vec![];
+                let returns_multiple = function_types
+                    .get(defined_index)
+                    .and_then(|ty| multi_value_types.get(*ty as usize))
+                    .copied()
+                    .unwrap_or(false);
+                if returns_multiple {
+                    features.push(WasmFeature::MultiValue);
+                }
+                for operator in body.get_operators_reader()? {
+                    if let Some(feature) = WasmFeature::of_operator(&operator?) {
+                        if !features.contains(&feature) {
+                            features.push(feature);
+                        }
+                    }
+                }
+                function_features.push(features);
+            }
+            Payload::CustomSection(section) => {
+                if let KnownCustom::Name(reader) = section.as_known() {
+                    // A malformed name section only costs us the function names
+                    for name in reader.into_iter().flatten() {
+                        if let Name::Function(map) = name {
+                            for naming in map.into_iter().flatten() {
+                                names.insert(naming.index, naming.name.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if let Some((id, range)) = payload.as_section() {
+            let name = match &payload {
+                Payload::CustomSection(section) => format!("custom:{}", section.name()),
+                _ => section_name(id).to_string(),
+            };
+            analysis.sections.push(SectionSize {
+                name,
+                size: range.len() as u64,
+            });
+        }
+    }
+
+    for function in &mut analysis.functions {
+        function.name = names.remove(&function.index);
+    }
+
+    for (function, features) in analysis.functions.iter().zip(function_features) {
+        for feature in features {
+            analysis
+                .features
+                .entry(feature)
+                .or_default()
+                .push(function.display_name());
+        }
+    }
+    if analysis.memories.iter().any(|m| m.shared) {
+        analysis.features.entry(WasmFeature::Threads).or_default();
+    }
+    if analysis.tables.len() > 1 {
+        analysis
+            .features
+            .entry(WasmFeature::ReferenceTypes)
+            .or_default();
+    }
+
+    Ok(analysis)
+}
+
+/// Reads an unsigned LEB128 integer, returning it and the number of bytes it took
+///
+/// * `bytes` - The bytes starting with the integer
+fn read_leb128(bytes: &[u8]) -> Result<(u64, usize)> {
+    let mut value = 0;
+    for (i, byte) in bytes.iter().enumerate().take(10) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+
+    Err(anyhow!("malformed LEB128 integer"))
+}
+
+/// Removes every custom section from a WASM module: the name section, DWARF debug info and the `producers` and `target_features` sections the linker adds
+///
+/// * `wasm` - The WASM module
+pub fn strip_custom_sections(wasm: &[u8]) -> Result<Vec<u8>> {
+    let header = wasm.get(..8).ok_or(anyhow!("module is too small"))?;
+    let mut stripped = header.to_vec();
+
+    let mut offset = 8;
+    while offset < wasm.len() {
+        let id = wasm[offset];
+        let (size, leb_size) = read_leb128(&wasm[offset + 1..])?;
+        let contents_start = offset + 1 + leb_size;
+        let end = contents_start + size as usize;
+        if end > wasm.len() {
+            return Err(anyhow!("section extends past the end of the module"));
+        }
+
+        if id != 0 {
+            stripped.extend_from_slice(&wasm[offset..end]);
+        }
+
+        offset = end;
+    }
+
+    Ok(stripped)
+}
+
+/// Writes an unsigned LEB128 integer
+///
+/// * `value` - The integer
+/// * `out` - Where to write the integer to
+fn write_leb128(mut value: u64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Appends a custom section to the end of a WASM module
+///
+/// * `wasm` - The WASM module
+/// * `name` - The name of the section
+/// * `data` - The contents of the section
+pub fn append_custom_section(wasm: &mut Vec<u8>, name: &str, data: &[u8]) {
+    let mut contents = vec![];
+    write_leb128(name.len() as u64, &mut contents);
+    contents.extend_from_slice(name.as_bytes());
+    contents.extend_from_slice(data);
+
+    wasm.push(0);
+    write_leb128(contents.len() as u64, wasm);
+    wasm.extend_from_slice(&contents);
+}
@@ -0,0 +1,598 @@
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use cargo_metadata::{diagnostic::DiagnosticLevel as CompilerLevel, Message};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use wasm_opt::{Feature, OptimizationError, OptimizationOptions, Pass};
+
+use crate::{
+    analysis::{analyze_module, append_custom_section, strip_custom_sections},
+    layout::{write_layout, LAYOUT_FILE_NAME},
+    lint::check_crate,
+    lock::check_lock,
+    metadata::get_crate_metadata,
+    provenance::{BuildInfo, BUILD_INFO_SECTION},
+    report::{format_size, read_previous_report, save_report, BuildReport},
+    reproducible::{get_path_remaps, get_source_date_epoch, get_whitelisted_env},
+    validate::validate_module,
+    SdkManager, SimulatorVersion,
+};
+
+// The wasm-opt passes run on modules on top of -O1, and the WASM features they may use, by their wasm-opt names
+const WASM_OPT_PASSES: &[Pass] = &[Pass::SignextLowering];
+const WASM_OPT_FEATURES: &[&str] = &["bulk-memory"];
+
+#[cfg(target_os = "windows")]
+const BUILT_INS_PATH: &str = ".\\lib\\wasm32-wasi\\libclang_rt.builtins-wasm32.a";
+#[cfg(not(target_os = "windows"))]
+const BUILT_INS_PATH: &str = "./lib/wasm32-wasi/libclang_rt.builtins-wasm32.a";
+
+#[cfg(target_os = "windows")]
+const WASI_PATH: &str = ".\\lib\\wasm32-wasi";
+#[cfg(not(target_os = "windows"))]
+const WASI_PATH: &str = "./lib/wasm32-wasi";
+
+/// Where the output of a build is written to
+#[derive(Debug, Clone)]
+pub enum BuildOutput {
+    /// The full path (including filename) of a single WASM file
+    Wasm(String),
+    /// The root of an MSFS package. The module is placed at the module path declared in the crate's metadata
+    Package(PathBuf),
+}
+
+/// An error while building a crate
+#[derive(Debug, Error)]
+pub enum BuildError {
+    /// cargo couldn't be run or didn't report a finished build
+    #[error("{0}")]
+    CargoFailed(String),
+    /// The crate failed to compile. The compiler messages are passed to the observer as diagnostics
+    #[error("build did not finish successfully")]
+    CompileErrors,
+    /// The build didn't produce exactly one module
+    #[error("{0}")]
+    ArtifactAmbiguous(String),
+    /// wasm-opt failed to optimize the module
+    #[error("wasm-opt failed")]
+    WasmOpt(#[source] OptimizationError),
+    /// The crate or module doesn't meet the sim's requirements or the crate's size budget
+    #[error("{0}")]
+    Validation(String),
+}
+
+/// What to build and where to write it
+#[derive(Debug, Clone)]
+pub struct BuildPlan {
+    /// The simulator version to build for
+    pub simulator: SimulatorVersion,
+    /// The path to the crate to build
+    pub in_folder: String,
+    /// Where to write the compiled WASM file
+    pub output: BuildOutput,
+    /// Whether to build so the same commit produces the same bytes on any machine
+    pub reproducible: bool,
+    /// Whether to save the size report next to cargo's artifact, for the next build to compare against
+    pub save_report: bool,
+}
+
+impl BuildPlan {
+    /// Creates a plan to build a crate for the given simulator
+    ///
+    /// * `simulator` - The simulator version to build for
+    /// * `in_folder` - The path to the crate to build
+    /// * `output` - Where to write the compiled WASM file
+    pub fn new(
+        simulator: SimulatorVersion,
+        in_folder: impl Into<String>,
+        output: BuildOutput,
+    ) -> Self {
+        Self {
+            simulator,
+            in_folder: in_folder.into(),
+            output,
+            reproducible: false,
+            save_report: true,
+        }
+    }
+
+    /// Sets whether to build so the same commit produces the same bytes on any machine: machine specific paths are remapped, only a whitelisted environment is passed to cargo and SOURCE_DATE_EPOCH is pinned
+    ///
+    /// * `reproducible` - Whether to build reproducibly
+    pub fn reproducible(mut self, reproducible: bool) -> Self {
+        self.reproducible = reproducible;
+        self
+    }
+
+    /// Sets whether to save the size report of the build for the next build to compare against. Builds that aren't meant to be kept, like rebuilds verifying an artifact, shouldn't
+    ///
+    /// * `save_report` - Whether to save the report
+    pub fn save_report(mut self, save_report: bool) -> Self {
+        self.save_report = save_report;
+        self
+    }
+}
+
+/// A step of a build
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BuildStage {
+    /// Checking the crate's manifest and SDK lock
+    Check,
+    /// Compiling the crate with cargo
+    Compile,
+    /// Running wasm-opt
+    Optimize,
+    /// Embedding build info and measuring the module
+    Analyze,
+    /// Checking the module against the size budget and the sim's supported features
+    Validate,
+    /// Writing the module and the package layout
+    Write,
+}
+
+/// How severe a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticLevel {
+    Error,
+    Warning,
+    Note,
+}
+
+/// A message about a build, from cargo-msfs itself or the compiler
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub level: DiagnosticLevel,
+    pub message: String,
+    /// The message as rendered by the compiler, including the source snippet, for compiler messages
+    pub rendered: Option<String>,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic that didn't come from the compiler
+    ///
+    /// * `level` - How severe the diagnostic is
+    /// * `message` - The message
+    pub fn new(level: DiagnosticLevel, message: String) -> Self {
+        Self {
+            level,
+            message,
+            rendered: None,
+        }
+    }
+}
+
+/// Receives progress and diagnostics as a build runs
+pub trait BuildObserver {
+    /// Called when the build moves on to a new stage
+    ///
+    /// * `stage` - The stage the build is now in
+    fn stage(&mut self, _stage: BuildStage) {}
+
+    /// Called for every diagnostic, as soon as it's emitted. Diagnostics leading up to a failed build are only available this way
+    ///
+    /// * `diagnostic` - The diagnostic
+    fn diagnostic(&mut self, _diagnostic: &Diagnostic) {}
+}
+
+/// Ignores progress and diagnostics
+impl BuildObserver for () {}
+
+/// Forwards to another observer while keeping every diagnostic
+struct CollectingObserver<'a> {
+    inner: &'a mut dyn BuildObserver,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl BuildObserver for CollectingObserver<'_> {
+    fn stage(&mut self, stage: BuildStage) {
+        self.inner.stage(stage);
+    }
+
+    fn diagnostic(&mut self, diagnostic: &Diagnostic) {
+        self.inner.diagnostic(diagnostic);
+        self.diagnostics.push(diagnostic.clone());
+    }
+}
+
+/// What a file written by a build is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    /// The optimized WASM module
+    Module,
+    /// The regenerated `layout.json` of a package
+    Layout,
+}
+
+/// A file written by a build
+#[derive(Debug, Clone, Serialize)]
+pub struct Artifact {
+    pub kind: ArtifactKind,
+    pub path: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl Artifact {
+    /// Describes a written file
+    ///
+    /// * `kind` - What the file is
+    /// * `path` - The path of the file
+    /// * `contents` - The contents of the file
+    fn new(kind: ArtifactKind, path: PathBuf, contents: &[u8]) -> Self {
+        Self {
+            kind,
+            path,
+            size: contents.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(contents)),
+        }
+    }
+}
+
+/// The outcome of a successful build
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildResult {
+    /// The files written by the build, the module first
+    pub artifacts: Vec<Artifact>,
+    /// The size report of the module
+    pub report: BuildReport,
+    /// The size report of the previous build of the crate, if there was one
+    pub previous_report: Option<BuildReport>,
+    /// The build info embedded in the module
+    pub build_info: BuildInfo,
+    /// Every diagnostic emitted during the build
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl BuildResult {
+    /// Gets the path of the written module
+    pub fn module_path(&self) -> &Path {
+        &self.artifacts[0].path
+    }
+}
+
+/// Gets the wasm-opt options modules are optimized with, along with the equivalent wasm-opt arguments recorded in their build info
+fn wasm_opt_options() -> Result<(OptimizationOptions, String)> {
+    let mut options = OptimizationOptions::new_opt_level_1();
+    let mut arguments = vec![format!("-O{}", options.passopts.optimize_level as u8)];
+    for pass in WASM_OPT_PASSES {
+        options.add_pass(pass.clone());
+        arguments.push(format!("--{}", pass.name()));
+    }
+    for feature in WASM_OPT_FEATURES {
+        options.enable_feature(Feature::from_str(feature)?);
+        arguments.push(format!("--enable-{feature}"));
+    }
+
+    Ok((options, arguments.join(" ")))
+}
+
+/// Maps a compiler message to a diagnostic
+///
+/// * `message` - The compiler message
+fn compiler_diagnostic(message: &cargo_metadata::diagnostic::Diagnostic) -> Diagnostic {
+    Diagnostic {
+        level: match message.level {
+            CompilerLevel::Ice | CompilerLevel::Error => DiagnosticLevel::Error,
+            CompilerLevel::Warning => DiagnosticLevel::Warning,
+            _ => DiagnosticLevel::Note,
+        },
+        message: message.message.clone(),
+        rendered: message.rendered.clone(),
+    }
+}
+
+/// Builds crates against the SDKs of an [`SdkManager`]
+#[derive(Debug, Clone)]
+pub struct Builder {
+    sdk_manager: SdkManager,
+}
+
+impl Builder {
+    /// Creates a builder using the SDKs installed by the given manager
+    ///
+    /// * `sdk_manager` - The manager of the SDK to build against
+    pub fn new(sdk_manager: SdkManager) -> Self {
+        Self { sdk_manager }
+    }
+
+    /// Gets the manager of the SDK crates are built against
+    pub fn sdk_manager(&self) -> &SdkManager {
+        &self.sdk_manager
+    }
+
+    /// Builds a crate and writes the module to the plan's output
+    ///
+    /// When building into a package, the package's `layout.json` is regenerated afterwards since the sim won't load a module with a stale entry
+    ///
+    /// * `plan` - What to build and where to write it
+    /// * `observer` - Receives progress and diagnostics as the build runs
+    pub fn build(&self, plan: &BuildPlan, observer: &mut dyn BuildObserver) -> Result<BuildResult> {
+        let mut observer = CollectingObserver {
+            inner: observer,
+            diagnostics: vec![],
+        };
+
+        let mut result = match &plan.output {
+            BuildOutput::Wasm(out_wasm) => self.build_module(
+                plan,
+                &PathBuf::from(out_wasm.replace("\\", "/")),
+                &mut observer,
+            )?,
+            BuildOutput::Package(package_folder) => {
+                let (package, msfs_metadata) = get_crate_metadata(&plan.in_folder)?;
+                let out_path = package_folder.join(msfs_metadata.module_path(&package));
+                fs::create_dir_all(out_path.parent().context("could not get parent")?)?;
+
+                let mut result = self.build_module(plan, &out_path, &mut observer)?;
+                write_layout(package_folder)?;
+                let layout_path = package_folder.join(LAYOUT_FILE_NAME);
+                result.artifacts.push(Artifact::new(
+                    ArtifactKind::Layout,
+                    layout_path.clone(),
+                    &fs::read(&layout_path)?,
+                ));
+
+                result
+            }
+        };
+        result.diagnostics = observer.diagnostics;
+
+        Ok(result)
+    }
+
+    /// Runs a cargo subcommand on a crate with the flags and environment needed to compile for the given simulator
+    ///
+    /// Compiler messages are passed to the observer as diagnostics
+    ///
+    /// * `sim_version` - The simulator version to compile for
+    /// * `in_folder` - The path to the crate
+    /// * `subcommand_args` - The cargo subcommand and its arguments, e.g. `["build"]`
+    /// * `reproducible` - Whether to remap machine specific paths and only pass a whitelisted environment to cargo
+    /// * `observer` - Receives diagnostics
+    pub fn run_cargo(
+        &self,
+        sim_version: SimulatorVersion,
+        in_folder: &str,
+        subcommand_args: &[&str],
+        reproducible: bool,
+        observer: &mut dyn BuildObserver,
+    ) -> Result<Vec<Message>> {
+        // Assure we downloaded the SDK and locate its wasi-sysroot
+        let (sdk_path, wasi_sysroot_path) = self.sdk_manager.resolve_paths(sim_version)?;
+        // Make sure the crate is built with the SDK it's locked to
+        check_lock(in_folder, sim_version, &self.sdk_manager, observer)?;
+
+        // Construct the build flags
+        let mut flags = [
+            // Keep the name section for the build report, custom sections are stripped after wasm-opt. This comes after the profile's `strip` so it takes precedence
+            "-Cstrip=debuginfo",
+            "-Clto",
+            "-Ctarget-feature=-crt-static,+bulk-memory",
+            "-Clink-self-contained=no",
+            "-Clink-arg=-l",
+            "-Clink-arg=c",
+            &format!(
+                "-Clink-arg={}",
+                wasi_sysroot_path.join(BUILT_INS_PATH).to_string_lossy()
+            ),
+            "-Clink-arg=-L",
+            &format!(
+                "-Clink-arg={}",
+                wasi_sysroot_path.join(WASI_PATH).to_string_lossy()
+            ),
+            "-Clink-arg=--export-table",
+            "-Clink-arg=--allow-undefined",
+            "-Clink-arg=--export-dynamic",
+            "-Clink-arg=--export=__wasm_call_ctors",
+            "-Clink-arg=--export=malloc",
+            "-Clink-arg=--export=free",
+            "-Clink-arg=--export=mark_decommit_pages",
+            "-Clink-arg=--export=mallinfo",
+            "-Clink-arg=--export=mchunkit_begin",
+            "-Clink-arg=--export=mchunkit_next",
+            "-Clink-arg=--export=get_pages_state",
+        ]
+        .map(String::from)
+        .to_vec();
+        let mut c_flags = vec![format!("--sysroot={}", wasi_sysroot_path.to_string_lossy())];
+
+        let mut command = Command::new("cargo");
+        command.args(subcommand_args).args([
+            "--release",
+            "--target",
+            "wasm32-wasip1",
+            "--message-format=json",
+        ]);
+        if reproducible {
+            // Replace machine specific paths that end up in panic messages and debug info
+            for (from, to) in get_path_remaps(in_folder, &sdk_path)? {
+                flags.push(format!(
+                    "--remap-path-prefix={}={to}",
+                    from.to_string_lossy()
+                ));
+                c_flags.push(format!("-ffile-prefix-map={}={to}", from.to_string_lossy()));
+            }
+            command
+                .env_clear()
+                .envs(get_whitelisted_env())
+                .env("SOURCE_DATE_EPOCH", get_source_date_epoch(in_folder))
+                .env("CARGO_INCREMENTAL", "0");
+        }
+        command
+            .env("WASI_SYSROOT", wasi_sysroot_path.as_os_str())
+            .env("MSFS_SDK", &sdk_path)
+            .env("RUSTFLAGS", flags.join(" "))
+            .env("CFLAGS", c_flags.join(" "));
+        if !reproducible {
+            command.envs(std::env::vars()); // Inherit current environment variables
+        }
+
+        // Run cargo, capture output
+        let command = command
+            .current_dir(in_folder.replace("\\", "/"))
+            .stdout(Stdio::piped())
+            .spawn()
+            .and_then(|c| c.wait_with_output())
+            .map_err(|e| BuildError::CargoFailed(format!("couldn't run cargo: {e}")))?;
+
+        // Map the JSON stdout to structures
+        let messages = Message::parse_stream(Cursor::new(command.stdout))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| BuildError::CargoFailed(format!("couldn't read cargo output: {e}")))?;
+
+        // Pass on the compiler messages to guide the user on what went wrong
+        for message in &messages {
+            if let Message::CompilerMessage(compiler_message) = message {
+                observer.diagnostic(&compiler_diagnostic(&compiler_message.message));
+            }
+        }
+
+        // Ensure build finished and did so successfully
+        let Some(Message::BuildFinished(data)) = messages.last() else {
+            return Err(BuildError::CargoFailed("build didn't finish".to_string()).into());
+        };
+        if !data.success {
+            return Err(BuildError::CompileErrors.into());
+        }
+
+        Ok(messages)
+    }
+
+    /// Builds a crate for the given simulator, runs wasm-opt on the output and writes the module
+    ///
+    /// The module gets a custom section recording how it was built. Unless the plan says otherwise, a size report is saved to compare the next build against, and the build fails if the module exceeds the crate's size budget or uses WASM features the sim doesn't support
+    ///
+    /// * `plan` - What to build
+    /// * `out_path` - The full path (including filename) to output the compiled WASM file
+    /// * `observer` - Receives progress and diagnostics
+    fn build_module(
+        &self,
+        plan: &BuildPlan,
+        out_path: &Path,
+        observer: &mut dyn BuildObserver,
+    ) -> Result<BuildResult> {
+        let sim_version = plan.simulator;
+        let in_folder = plan.in_folder.as_str();
+
+        // Catch manifest misconfigurations before spending time on a build that won't load in the sim
+        observer.stage(BuildStage::Check);
+        check_crate(in_folder, observer)?;
+        let (package, msfs_metadata) = get_crate_metadata(in_folder)?;
+
+        observer.stage(BuildStage::Compile);
+        let messages = self.run_cargo(
+            sim_version,
+            in_folder,
+            &["build"],
+            plan.reproducible,
+            observer,
+        )?;
+
+        // Find the output artifacts
+        let out_artifact = messages
+            .iter()
+            .filter_map(|x| {
+                if let Message::CompilerArtifact(data) = x {
+                    Some(data)
+                } else {
+                    None
+                }
+            })
+            .next_back()
+            .ok_or(BuildError::ArtifactAmbiguous(
+                "couldn't get out artifact".to_string(),
+            ))?;
+
+        if out_artifact.filenames.len() > 1 {
+            return Err(BuildError::ArtifactAmbiguous(
+                "more than one file outputted for artifact, unsure how to proceed".to_string(),
+            )
+            .into());
+        }
+
+        // Run wasm-opt
+        observer.stage(BuildStage::Optimize);
+        let path = out_artifact
+            .filenames
+            .first()
+            .ok_or(BuildError::ArtifactAmbiguous("no filenames".to_string()))?;
+        let optimized_path = path.with_extension("opt.wasm");
+
+        let (mut wasm_opt, wasm_opt_arguments) = wasm_opt_options()?;
+        wasm_opt
+            .debug_info(true)
+            .run(path, &optimized_path)
+            .map_err(BuildError::WasmOpt)?;
+
+        // Report on the optimized module while it still has function names, then strip them along with the other custom sections the linker kept. Only the build info below is shipped
+        observer.stage(BuildStage::Analyze);
+        let named = fs::read(&optimized_path)?;
+        let mut optimized = strip_custom_sections(&named)?;
+        // The size budget is for the module's own code and data, which the build info below isn't
+        let budgeted_size = optimized.len() as u64;
+
+        // Record where the module came from so bug reports can be traced back to a build
+        let build_info = BuildInfo::collect(
+            sim_version,
+            &package,
+            self.sdk_manager.installed_version(sim_version)?,
+            &wasm_opt_arguments,
+        );
+        append_custom_section(
+            &mut optimized,
+            BUILD_INFO_SECTION,
+            &serde_json::to_vec(&build_info)?,
+        );
+        let named_analysis = analyze_module(&named)?;
+        let report = BuildReport::new(
+            fs::metadata(path)?.len(),
+            &analyze_module(&optimized)?,
+            &named_analysis,
+        );
+        let previous_report = read_previous_report(path.as_std_path());
+        if plan.save_report {
+            save_report(path.as_std_path(), &report)?;
+        }
+
+        observer.stage(BuildStage::Validate);
+        if let Some(size_budget) = msfs_metadata.size_budget {
+            if budgeted_size > size_budget {
+                return Err(BuildError::Validation(format!(
+                    "module is {} over its size budget of {}",
+                    format_size(budgeted_size - size_budget),
+                    format_size(size_budget)
+                ))
+                .into());
+            }
+        }
+
+        // The sim fails to load modules using unsupported proposals without saying why
+        validate_module(&named_analysis, sim_version)?;
+
+        observer.stage(BuildStage::Write);
+        fs::write(out_path, &optimized)?;
+
+        Ok(BuildResult {
+            artifacts: vec![Artifact::new(
+                ArtifactKind::Module,
+                out_path.to_path_buf(),
+                &optimized,
+            )],
+            report,
+            previous_report,
+            build_info,
+            diagnostics: vec![],
+        })
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    fs,
+    path::{self, Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{layout::write_layout, print_info};
+
+/// How a package is placed into the Community folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeployMode {
+    /// Link the package folder, so later builds are picked up without deploying again
+    Symlink,
+    /// Copy the package folder
+    Copy,
+}
+
+// File written into copied deployments, holding the package folder they were copied from
+const DEPLOY_MARKER_FILE_NAME: &str = ".cargo-msfs-deploy";
+
+/// Resolves a package folder to an absolute path, even if it no longer exists
+///
+/// * `package_folder` - The root of the package
+fn resolve_package_folder(package_folder: &Path) -> Result<PathBuf> {
+    Ok(match package_folder.canonicalize() {
+        Ok(path) => path,
+        Err(_) => path::absolute(package_folder)?,
+    })
+}
+
+/// Gets the path a package is deployed to in the Community folder
+///
+/// * `package_folder` - The resolved root of the package
+/// * `community_folder` - The Community folder of the sim
+fn get_deployed_path(package_folder: &Path, community_folder: &Path) -> Result<PathBuf> {
+    let package_name = package_folder
+        .file_name()
+        .context("couldn't get package name")?
+        .to_owned();
+
+    Ok(community_folder.join(package_name))
+}
+
+/// Recursively copies a directory
+///
+/// * `from` - The directory to copy
+/// * `to` - The destination, which is created if it doesn't exist
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let out_path = to.join(entry.file_name());
+        if fs::metadata(entry.path())?.is_dir() {
+            copy_dir(&entry.path(), &out_path)?;
+        } else {
+            fs::copy(entry.path(), out_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a directory symlink
+///
+/// * `original` - The directory to link to
+/// * `link` - The path of the link
+fn symlink_dir(original: &Path, link: &Path) -> std::io::Result<()> {
+    #[cfg(target_os = "windows")]
+    return std::os::windows::fs::symlink_dir(original, link);
+    #[cfg(not(target_os = "windows"))]
+    return std::os::unix::fs::symlink(original, link);
+}
+
+/// Copies a package into the Community folder, marking the copy as deployed from the package
+///
+/// * `package_folder` - The resolved root of the package
+/// * `deployed_path` - Where to copy the package to
+fn copy_package(package_folder: &Path, deployed_path: &Path) -> Result<()> {
+    copy_dir(package_folder, deployed_path)?;
+    fs::write(
+        deployed_path.join(DEPLOY_MARKER_FILE_NAME),
+        package_folder.to_string_lossy().as_bytes(),
+    )?;
+
+    Ok(())
+}
+
+/// Removes a deployed package, whether it was linked or copied. Returns whether anything was removed
+///
+/// Only links to the package and copies marked as deployed from it are removed, so an unrelated package of the same name is never touched
+///
+/// * `package_folder` - The root of the package
+/// * `community_folder` - The Community folder of the sim
+pub fn undeploy_package(package_folder: &Path, community_folder: &Path) -> Result<bool> {
+    let package_folder = resolve_package_folder(package_folder)?;
+    let deployed_path = get_deployed_path(&package_folder, community_folder)?;
+    let Ok(metadata) = fs::symlink_metadata(&deployed_path) else {
+        return Ok(false);
+    };
+
+    if metadata.is_symlink() {
+        // The link may be dangling if the package folder was removed, so compare the target itself
+        let target = community_folder.join(fs::read_link(&deployed_path)?);
+        let target = target.canonicalize().or_else(|_| path::absolute(&target))?;
+        if target != package_folder {
+            return Err(anyhow!(
+                "{} links to {}, not to this package, refusing to remove it",
+                deployed_path.display(),
+                target.display()
+            ));
+        }
+
+        // Directory symlinks on Windows are removed like directories, but without touching their contents
+        #[cfg(target_os = "windows")]
+        fs::remove_dir(&deployed_path)?;
+        #[cfg(not(target_os = "windows"))]
+        fs::remove_file(&deployed_path)?;
+        return Ok(true);
+    }
+
+    // Never delete the package itself if it lives directly in the Community folder
+    if deployed_path.canonicalize()? == package_folder {
+        return Err(anyhow!(
+            "package folder is inside the Community folder, refusing to remove it"
+        ));
+    }
+
+    let deployed_from = fs::read_to_string(deployed_path.join(DEPLOY_MARKER_FILE_NAME)).ok();
+    if deployed_from.as_deref().map(Path::new) != Some(package_folder.as_path()) {
+        return Err(anyhow!(
+            "{} wasn't deployed from this package, refusing to remove it",
+            deployed_path.display()
+        ));
+    }
+    fs::remove_dir_all(&deployed_path)?;
+
+    Ok(true)
+}
+
+/// Deploys a package into the Community folder, replacing a previous deployment. Returns the deployed path
+///
+/// The package's `layout.json` is regenerated first so the sim loads the current files
+///
+/// * `package_folder` - The root of the package
+/// * `community_folder` - The Community folder of the sim
+/// * `mode` - Whether to link or copy the package
+pub fn deploy_package(
+    package_folder: &Path,
+    community_folder: &Path,
+    mode: DeployMode,
+) -> Result<PathBuf> {
+    if !community_folder.is_dir() {
+        return Err(anyhow!(
+            "Community folder {} does not exist",
+            community_folder.display()
+        ));
+    }
+
+    write_layout(package_folder)?;
+    undeploy_package(package_folder, community_folder)?;
+
+    let package_folder = package_folder.canonicalize()?;
+    let deployed_path = get_deployed_path(&package_folder, community_folder)?;
+    match mode {
+        DeployMode::Symlink => {
+            if let Err(e) = symlink_dir(&package_folder, &deployed_path) {
+                // Creating symlinks on Windows requires developer mode or elevated privileges
+                print_info(&format!("Couldn't link package ({e}), copying instead"));
+                copy_package(&package_folder, &deployed_path)?;
+            }
+        }
+        DeployMode::Copy => copy_package(&package_folder, &deployed_path)?,
+    }
+
+    Ok(deployed_path)
+}
@@ -0,0 +1,83 @@
+use serde::Serialize;
+
+use crate::{build::BuildError, sdk::SdkError};
+
+/// What kind of failure an error is, so scripts can react to each differently. Every kind has a stable exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    /// Anything not covered by another kind. Exit code 1
+    Other,
+    /// The SDK for the simulator isn't installed. Exit code 10
+    SdkNotInstalled,
+    /// The manifest of available SDK versions couldn't be fetched or is invalid. Exit code 11
+    ManifestFetch,
+    /// The SDK installer couldn't be downloaded. Exit code 12
+    Download,
+    /// The SDK installer couldn't be read. Exit code 13
+    MsiParse,
+    /// The SDK files couldn't be extracted from the installer. Exit code 14
+    Extraction,
+    /// cargo couldn't be run or didn't report a finished build. Exit code 20
+    CargoFailed,
+    /// The crate failed to compile. Exit code 21
+    CompileErrors,
+    /// The build didn't produce exactly one module. Exit code 22
+    ArtifactAmbiguous,
+    /// wasm-opt failed to optimize the module. Exit code 23
+    WasmOpt,
+    /// The crate or module doesn't meet the sim's requirements or the crate's size budget. Exit code 24
+    Validation,
+}
+
+impl ErrorKind {
+    /// Classifies an error by the first typed error in its chain
+    ///
+    /// * `error` - The error to classify
+    pub fn of(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(error) = cause.downcast_ref::<SdkError>() {
+                return match error {
+                    SdkError::NotInstalled => Self::SdkNotInstalled,
+                    SdkError::ManifestFetch(_) | SdkError::InvalidManifest(_) => {
+                        Self::ManifestFetch
+                    }
+                    SdkError::Download(_) | SdkError::InstallerMismatch { .. } => Self::Download,
+                    SdkError::MsiParse(_) => Self::MsiParse,
+                    SdkError::Extraction(_) | SdkError::MissingCabinets(_) => Self::Extraction,
+                    SdkError::DataDir | SdkError::ReleaseNotFound(_) | SdkError::Io(_) => {
+                        Self::Other
+                    }
+                };
+            }
+            if let Some(error) = cause.downcast_ref::<BuildError>() {
+                return match error {
+                    BuildError::CargoFailed(_) => Self::CargoFailed,
+                    BuildError::CompileErrors => Self::CompileErrors,
+                    BuildError::ArtifactAmbiguous(_) => Self::ArtifactAmbiguous,
+                    BuildError::WasmOpt(_) => Self::WasmOpt,
+                    BuildError::Validation(_) => Self::Validation,
+                };
+            }
+        }
+
+        Self::Other
+    }
+
+    /// Gets the exit code the CLI exits with for this kind of error
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Other => 1,
+            Self::SdkNotInstalled => 10,
+            Self::ManifestFetch => 11,
+            Self::Download => 12,
+            Self::MsiParse => 13,
+            Self::Extraction => 14,
+            Self::CargoFailed => 20,
+            Self::CompileErrors => 21,
+            Self::ArtifactAmbiguous => 22,
+            Self::WasmOpt => 23,
+            Self::Validation => 24,
+        }
+    }
+}
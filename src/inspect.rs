@@ -0,0 +1,158 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    analysis::{analyze_module, Limits, ModuleAnalysis, WasmFeature},
+    print_info, print_warning,
+    provenance::{read_build_info, BuildInfo},
+    report::format_size,
+    validate::{describe_feature_use, disallowed_features},
+    SimulatorVersion,
+};
+
+/// Everything known about a WASM module
+#[derive(Debug, Serialize)]
+pub struct InspectReport {
+    /// How the module was built, if it was built by cargo-msfs
+    pub build_info: Option<BuildInfo>,
+    #[serde(flatten)]
+    pub analysis: ModuleAnalysis,
+    /// Things in the module known to break in MSFS
+    pub issues: Vec<String>,
+}
+
+/// Finds things in a module known to break in MSFS, once per feature along with every sim it breaks in
+///
+/// * `analysis` - The analysis of the module
+/// * `build_info` - The build info of the module, to only check against the sim it was built for
+fn find_issues(analysis: &ModuleAnalysis, build_info: Option<&BuildInfo>) -> Vec<String> {
+    let sims = match build_info {
+        Some(build_info) => vec![build_info.simulator],
+        None => vec![SimulatorVersion::Msfs2020, SimulatorVersion::Msfs2024],
+    };
+
+    analysis
+        .features
+        .keys()
+        .filter_map(|feature| {
+            let unsupported_by = sims
+                .iter()
+                .filter(|sim| disallowed_features(**sim).contains(feature))
+                .map(|sim| format!("{sim:?}"))
+                .collect::<Vec<_>>();
+            if unsupported_by.is_empty() {
+                return None;
+            }
+
+            let usage = describe_feature_use(analysis, *feature)?;
+            let issue = format!("not supported by {}: {usage}", unsupported_by.join(" and "));
+            Some(if *feature == WasmFeature::SignExt {
+                format!("{issue}. Sign-extension operators should have been removed by wasm-opt's SignextLowering pass")
+            } else {
+                issue
+            })
+        })
+        .collect()
+}
+
+/// Analyzes a WASM module and reads its build info
+///
+/// * `wasm` - The WASM module
+pub fn inspect_module(wasm: &[u8]) -> Result<InspectReport> {
+    let analysis = analyze_module(wasm)?;
+    let build_info = read_build_info(wasm)?;
+
+    Ok(InspectReport {
+        issues: find_issues(&analysis, build_info.as_ref()),
+        build_info,
+        analysis,
+    })
+}
+
+/// Formats memory or table limits
+///
+/// Example: `17 initial, 256 maximum` or `1 initial, no maximum`
+///
+/// * `limits` - The limits
+fn format_limits(limits: &Limits) -> String {
+    format!(
+        "{} initial, {}{}",
+        limits.initial,
+        limits
+            .maximum
+            .map(|m| format!("{m} maximum"))
+            .unwrap_or("no maximum".to_string()),
+        if limits.shared { ", shared" } else { "" }
+    )
+}
+
+impl InspectReport {
+    /// Prints the report
+    pub fn print(&self) {
+        match &self.build_info {
+            Some(build_info) => {
+                for line in build_info.summary() {
+                    print_info(&line);
+                }
+            }
+            None => print_info("Module has no build info, it wasn't built by cargo-msfs"),
+        }
+
+        let analysis = &self.analysis;
+        print_info(&format!("Size: {}", format_size(analysis.size)));
+        for (i, memory) in analysis.memories.iter().enumerate() {
+            print_info(&format!("Memory {i} (pages): {}", format_limits(memory)));
+        }
+        for (i, table) in analysis.tables.iter().enumerate() {
+            print_info(&format!(
+                "Table {i}: {} {}",
+                table.element_type.as_deref().unwrap_or_default(),
+                format_limits(table)
+            ));
+        }
+
+        print_info(&format!("Imports ({}):", analysis.imports.len()));
+        for import in &analysis.imports {
+            print_info(&format!(
+                "  {}.{} ({})",
+                import.module.as_deref().unwrap_or_default(),
+                import.name,
+                import.kind
+            ));
+        }
+        print_info(&format!("Exports ({}):", analysis.exports.len()));
+        for export in &analysis.exports {
+            print_info(&format!("  {} ({})", export.name, export.kind));
+        }
+
+        let features = analysis
+            .features
+            .keys()
+            .map(|f| f.name())
+            .collect::<Vec<_>>();
+        print_info(&format!(
+            "Features: {}",
+            if features.is_empty() {
+                "none beyond the MVP".to_string()
+            } else {
+                features.join(", ")
+            }
+        ));
+
+        let custom_sections = analysis
+            .sections
+            .iter()
+            .filter_map(|s| Some((s.name.strip_prefix("custom:")?, s.size)))
+            .collect::<Vec<_>>();
+        if !custom_sections.is_empty() {
+            print_info("Custom sections:");
+            for (name, size) in custom_sections {
+                print_info(&format!("  {name} ({})", format_size(size)));
+            }
+        }
+
+        for issue in &self.issues {
+            print_warning(issue);
+        }
+    }
+}
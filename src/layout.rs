@@ -0,0 +1,94 @@
+use std::{
+    fs::{self, File},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+// File names at the package root that are not listed in the layout
+pub const LAYOUT_FILE_NAME: &str = "layout.json";
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+// Number of 100 nanosecond intervals between the Windows FILETIME epoch (1601-01-01) and the Unix epoch
+const FILETIME_UNIX_EPOCH_OFFSET: u64 = 116_444_736_000_000_000;
+
+/// A single file entry in `layout.json`
+#[derive(Debug, Serialize)]
+struct LayoutEntry {
+    /// Path relative to the package root, always using forward slashes
+    path: String,
+    /// Size in bytes
+    size: u64,
+    /// Last modification time as a Windows FILETIME
+    date: u64,
+}
+
+/// The contents of `layout.json`
+#[derive(Debug, Serialize)]
+struct Layout {
+    content: Vec<LayoutEntry>,
+}
+
+/// Recursively collects the layout entries of every file under a directory
+///
+/// * `package_folder` - The package root, used to compute relative paths
+/// * `directory` - The directory to collect from
+/// * `entries` - The vec to push entries into
+fn collect_entries(
+    package_folder: &Path,
+    directory: &Path,
+    entries: &mut Vec<LayoutEntry>,
+) -> Result<()> {
+    for dir_entry in fs::read_dir(directory)? {
+        let dir_entry = dir_entry?;
+        let path = dir_entry.path();
+        // Follow symlinks so linked content is listed like regular files
+        let metadata = fs::metadata(&path)?;
+
+        if metadata.is_dir() {
+            collect_entries(package_folder, &path, entries)?;
+            continue;
+        }
+
+        let relative_path = path.strip_prefix(package_folder)?;
+        if relative_path == Path::new(LAYOUT_FILE_NAME)
+            || relative_path == Path::new(MANIFEST_FILE_NAME)
+        {
+            continue;
+        }
+
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .context("file modification time is before the unix epoch")?;
+
+        entries.push(LayoutEntry {
+            path: relative_path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            size: metadata.len(),
+            date: FILETIME_UNIX_EPOCH_OFFSET + (modified.as_nanos() / 100) as u64,
+        });
+    }
+
+    Ok(())
+}
+
+/// Regenerates the `layout.json` of a package from the files currently on disk. Returns the total size of the listed files
+///
+/// * `package_folder` - The root of the package
+pub fn write_layout(package_folder: &Path) -> Result<u64> {
+    let mut content = vec![];
+    collect_entries(package_folder, package_folder, &mut content)?;
+    content.sort_by(|a, b| a.path.cmp(&b.path));
+    let total_size = content.iter().map(|e| e.size).sum();
+
+    let file = File::create(package_folder.join(LAYOUT_FILE_NAME))?;
+    serde_json::to_writer_pretty(file, &Layout { content })?;
+
+    Ok(total_size)
+}
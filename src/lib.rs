@@ -0,0 +1,42 @@
+//! Library behind the `cargo-msfs` CLI
+//!
+//! [`SdkManager`] installs, updates, removes and locates the MSFS SDKs crates are built against, and [`Builder`] compiles, optimizes and validates crates against them, so tools other than the CLI can do both too.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// WASM module parsing
+pub mod analysis;
+/// Crate compilation and post-processing
+pub mod build;
+/// Error classification and exit codes
+pub mod error;
+/// MSFS package layout.json generation
+pub mod layout;
+/// Checking crate manifests for MSFS requirements
+pub mod lint;
+/// Pinning crates to SDK versions
+pub mod lock;
+/// Reading the `[package.metadata.msfs]` table of crates
+pub mod metadata;
+/// Build info embedded in modules
+pub mod provenance;
+/// Build size reports
+pub mod report;
+/// Reproducible build settings
+mod reproducible;
+/// SDK info and download utility
+pub mod sdk;
+/// Checking modules for WASM features the sims don't support
+pub mod validate;
+
+pub use build::{BuildError, BuildObserver, BuildOutput, BuildPlan, BuildResult, Builder};
+pub use error::ErrorKind;
+pub use sdk::{InstallOptions, InstallProgress, InstalledSdk, SdkComponent, SdkError, SdkManager};
+
+/// A specific version of MSFS
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum, Serialize, Deserialize)]
+pub enum SimulatorVersion {
+    Msfs2020,
+    Msfs2024,
+}
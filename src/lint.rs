@@ -0,0 +1,194 @@
+use std::fs;
+
+use anyhow::{Context, Result};
+use cargo_metadata::MetadataCommand;
+use toml::{Table, Value};
+
+use crate::build::{BuildError, BuildObserver, Diagnostic, DiagnosticLevel};
+
+/// How severe a lint issue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The module will likely still work, but is bigger or slower than it needs to be
+    Warning,
+    /// The module will be broken or rejected by the sim
+    Error,
+}
+
+/// A problem found in a crate's configuration
+#[derive(Debug, Clone)]
+pub struct LintIssue {
+    pub severity: Severity,
+    /// What's wrong
+    pub message: String,
+    /// The TOML to add to Cargo.toml to fix the issue
+    pub snippet: String,
+}
+
+impl LintIssue {
+    fn new(severity: Severity, message: &str, snippet: &str) -> Self {
+        Self {
+            severity,
+            message: message.to_string(),
+            snippet: snippet.to_string(),
+        }
+    }
+}
+
+/// Checks the release profile settings that matter for MSFS modules
+///
+/// * `profile` - The `[profile.release]` table, if there is one
+fn lint_profile(profile: Option<&Table>) -> Vec<LintIssue> {
+    let mut issues = vec![];
+    // Cargo lets `CARGO_PROFILE_RELEASE_*` override the manifest, so those win
+    let get = |key: &str| {
+        let env_key = format!(
+            "CARGO_PROFILE_RELEASE_{}",
+            key.to_uppercase().replace('-', "_")
+        );
+        match std::env::var(env_key) {
+            Ok(value) => Some(
+                value
+                    .parse::<bool>()
+                    .map(Value::Boolean)
+                    .or_else(|_| value.parse::<i64>().map(Value::Integer))
+                    .unwrap_or(Value::String(value)),
+            ),
+            Err(_) => profile.and_then(|p| p.get(key)).cloned(),
+        }
+    };
+
+    match get("lto").as_ref() {
+        Some(Value::Boolean(true)) => {}
+        Some(Value::String(lto)) if lto == "fat" => {}
+        Some(Value::String(lto)) if lto == "thin" => issues.push(LintIssue::new(
+            Severity::Warning,
+            "`lto = \"thin\"` produces larger modules than full LTO",
+            "[profile.release]\nlto = true",
+        )),
+        // Without it cargo builds dependencies with `-C embed-bitcode=no`, which rustc refuses to combine with the `-C lto` builds pass
+        _ => issues.push(LintIssue::new(
+            Severity::Error,
+            "LTO must be enabled in the release profile",
+            "[profile.release]\nlto = true",
+        )),
+    }
+
+    // `strip` isn't checked: builds override it to keep function names for the size report, and strip them after wasm-opt
+
+    // WASM can't unwind, so panics always abort. Asking for unwinding only adds unused landing pads
+    if let Some(Value::String(panic)) = get("panic").as_ref() {
+        if panic == "unwind" {
+            issues.push(LintIssue::new(
+                Severity::Warning,
+                "`panic = \"unwind\"` is not supported by WASM and only increases module size",
+                "[profile.release]\npanic = \"abort\"",
+            ));
+        }
+    }
+
+    match get("opt-level").as_ref() {
+        Some(Value::Integer(0)) => issues.push(LintIssue::new(
+            Severity::Warning,
+            "`opt-level = 0` produces very large and slow modules",
+            "[profile.release]\nopt-level = 3",
+        )),
+        Some(Value::String(level)) if level == "0" => issues.push(LintIssue::new(
+            Severity::Warning,
+            "`opt-level = \"0\"` produces very large and slow modules",
+            "[profile.release]\nopt-level = 3",
+        )),
+        _ => {}
+    }
+
+    if let Some(Value::Integer(units)) = get("codegen-units").as_ref() {
+        if *units > 1 {
+            issues.push(LintIssue::new(
+                Severity::Warning,
+                &format!("`codegen-units = {units}` limits optimization across the module"),
+                "[profile.release]\ncodegen-units = 1",
+            ));
+        }
+    }
+
+    issues
+}
+
+/// Checks a crate's manifest for settings that are missing or conflict with what MSFS modules need
+///
+/// Profiles are only read from the workspace root manifest by Cargo, so that's the one that is checked, along with `CARGO_PROFILE_RELEASE_*` variables. Profiles set in `.cargo/config.toml` aren't seen
+///
+/// * `in_folder` - The path to the crate
+pub fn lint_crate(in_folder: &str) -> Result<Vec<LintIssue>> {
+    let metadata = MetadataCommand::new()
+        .current_dir(in_folder.replace("\\", "/"))
+        .no_deps()
+        .exec()?;
+    let package = metadata
+        .root_package()
+        .context("couldn't find a root package, virtual workspaces are not supported")?;
+
+    let mut issues = vec![];
+
+    let is_cdylib = package
+        .targets
+        .iter()
+        .any(|t| t.crate_types.iter().any(|c| c == "cdylib"));
+    if !is_cdylib {
+        issues.push(LintIssue::new(
+            Severity::Error,
+            "the crate must be built as a `cdylib` to produce a WASM module",
+            "[lib]\ncrate-type = [\"cdylib\"]",
+        ));
+    }
+
+    let root_manifest_path = metadata.workspace_root.join("Cargo.toml");
+    let root_manifest = fs::read_to_string(&root_manifest_path)?
+        .parse::<Table>()
+        .with_context(|| format!("couldn't parse {root_manifest_path}"))?;
+    let profile = root_manifest
+        .get("profile")
+        .and_then(|p| p.get("release"))
+        .and_then(|r| r.as_table());
+    issues.extend(lint_profile(profile));
+
+    Ok(issues)
+}
+
+/// Lints a crate, reporting warnings and failing if any errors were found
+///
+/// * `in_folder` - The path to the crate
+/// * `observer` - Receives the warnings
+pub fn check_crate(in_folder: &str, observer: &mut dyn BuildObserver) -> Result<()> {
+    let issues = lint_crate(in_folder)?;
+
+    for issue in issues.iter().filter(|i| i.severity == Severity::Warning) {
+        observer.diagnostic(&Diagnostic::new(
+            DiagnosticLevel::Warning,
+            format!(
+                "{}. Add the following to Cargo.toml:\n{}",
+                issue.message, issue.snippet
+            ),
+        ));
+    }
+
+    let errors = issues
+        .iter()
+        .filter(|i| i.severity == Severity::Error)
+        .map(|i| {
+            format!(
+                "{}. Add the following to Cargo.toml:\n{}",
+                i.message, i.snippet
+            )
+        })
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        return Err(BuildError::Validation(format!(
+            "crate is misconfigured for MSFS:\n\n{}",
+            errors.join("\n\n")
+        ))
+        .into());
+    }
+
+    Ok(())
+}
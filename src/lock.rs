@@ -0,0 +1,148 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    build::{BuildObserver, Diagnostic, DiagnosticLevel},
+    sdk::is_same_sdk_version,
+    SdkManager, SimulatorVersion,
+};
+
+/// The name of the lockfile, next to the crate's Cargo.toml
+pub const LOCK_FILE_NAME: &str = "msfs.lock";
+
+// Written at the top of lockfiles
+const LOCK_FILE_HEADER: &str =
+    "# This file is generated by cargo-msfs. It pins the SDK versions the crate is built with\n\n";
+
+/// The SDK a crate is locked to for a simulator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LockedSdk {
+    pub simulator: SimulatorVersion,
+    pub version: String,
+    /// The SHA-256 of the SDK installer, if it was known when the lock was written
+    pub installer_sha256: Option<String>,
+}
+
+/// The contents of a lockfile
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LockFile {
+    #[serde(default)]
+    sdk: Vec<LockedSdk>,
+}
+
+/// Reads the lockfile of a crate, or an empty one if there is none
+///
+/// * `in_folder` - The path to the crate
+fn read_lock_file(in_folder: &str) -> Result<LockFile> {
+    let path = Path::new(&in_folder.replace("\\", "/")).join(LOCK_FILE_NAME);
+    match fs::read_to_string(&path) {
+        Ok(contents) => {
+            toml::from_str(&contents).with_context(|| format!("couldn't parse {}", path.display()))
+        }
+        Err(_) => Ok(LockFile::default()),
+    }
+}
+
+/// Gets the SDK a crate is locked to for a simulator
+///
+/// * `in_folder` - The path to the crate
+/// * `sim_version` - The simulator version
+pub fn get_locked_sdk(in_folder: &str, sim_version: SimulatorVersion) -> Result<Option<LockedSdk>> {
+    Ok(read_lock_file(in_folder)?
+        .sdk
+        .into_iter()
+        .find(|s| s.simulator == sim_version))
+}
+
+/// Gets the SDK installed for a simulator, as it would be locked
+///
+/// * `sim_version` - The simulator version
+/// * `sdk_manager` - The manager of the installed SDK
+fn get_installed_sdk(sim_version: SimulatorVersion, sdk_manager: &SdkManager) -> Result<LockedSdk> {
+    Ok(LockedSdk {
+        simulator: sim_version,
+        version: sdk_manager
+            .installed_version(sim_version)?
+            .ok_or(anyhow!("SDK not installed"))?,
+        installer_sha256: sdk_manager.installed_hash(sim_version)?,
+    })
+}
+
+/// Locks a crate to the SDK installed for a simulator, replacing the SDK it was locked to. Returns the locked SDK
+///
+/// * `in_folder` - The path to the crate
+/// * `sim_version` - The simulator version
+/// * `sdk_manager` - The manager of the installed SDK
+pub fn lock_installed_sdk(
+    in_folder: &str,
+    sim_version: SimulatorVersion,
+    sdk_manager: &SdkManager,
+) -> Result<LockedSdk> {
+    let installed = get_installed_sdk(sim_version, sdk_manager)?;
+
+    let mut lock_file = read_lock_file(in_folder)?;
+    lock_file.sdk.retain(|s| s.simulator != sim_version);
+    lock_file.sdk.push(installed.clone());
+    let path = Path::new(&in_folder.replace("\\", "/")).join(LOCK_FILE_NAME);
+    fs::write(
+        path,
+        format!("{LOCK_FILE_HEADER}{}", toml::to_string(&lock_file)?),
+    )?;
+
+    Ok(installed)
+}
+
+/// Checks the installed SDK matches the one a crate is locked to. Crates that aren't locked for the simulator are only noted, the lockfile is never written
+///
+/// * `in_folder` - The path to the crate
+/// * `sim_version` - The simulator version
+/// * `sdk_manager` - The manager of the installed SDK
+/// * `observer` - Receives notes and warnings about the lock
+pub fn check_lock(
+    in_folder: &str,
+    sim_version: SimulatorVersion,
+    sdk_manager: &SdkManager,
+    observer: &mut dyn BuildObserver,
+) -> Result<()> {
+    let installed = get_installed_sdk(sim_version, sdk_manager)?;
+
+    let lock_file = read_lock_file(in_folder)?;
+    let Some(locked) = lock_file.sdk.iter().find(|s| s.simulator == sim_version) else {
+        observer.diagnostic(&Diagnostic::new(
+            DiagnosticLevel::Note,
+            format!(
+                "crate isn't locked to a {sim_version:?} SDK version. Run the lock command to lock it to the installed version {}",
+                installed.version
+            ),
+        ));
+        return Ok(());
+    };
+
+    if !is_same_sdk_version(&locked.version, &installed.version) {
+        return Err(anyhow!(
+            "crate is locked to {sim_version:?} SDK version {}, but {} is installed. Run the install command with --locked to install the locked version, or run the lock command to lock the installed one",
+            locked.version,
+            installed.version
+        ));
+    }
+    match (&locked.installer_sha256, &installed.installer_sha256) {
+        (Some(locked_hash), Some(installed_hash)) if locked_hash != installed_hash => {
+            return Err(anyhow!(
+                "the installed {sim_version:?} SDK version {} was installed from a different installer than the one in {LOCK_FILE_NAME}. Reinstall it with the install command and --locked",
+                locked.version
+            ));
+        }
+        (Some(_), None) => observer.diagnostic(&Diagnostic::new(
+            DiagnosticLevel::Warning,
+            format!(
+                "the installer hash of the installed {sim_version:?} SDK is unknown, reinstall it to verify it against {LOCK_FILE_NAME}"
+            ),
+        )),
+        _ => {}
+    }
+
+    Ok(())
+}
@@ -1,42 +1,59 @@
 use std::{
-    io::Cursor,
+    fs,
     path::PathBuf,
-    process::{Command, Stdio},
-    time::Duration,
+    process::ExitCode,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{anyhow, Context, Result};
-use cargo_metadata::Message;
+use cargo_msfs::{
+    analysis, build, layout, lock, metadata, provenance, report,
+    sdk::{
+        get_release_number, is_same_sdk_version, InstalledComponent, RemoteSdk, CORE_INSTALLER_KEY,
+    },
+    validate, BuildOutput, BuildPlan, Builder, ErrorKind, InstallOptions, InstallProgress,
+    SdkComponent, SdkManager, SimulatorVersion,
+};
 use clap::{Parser, ValueEnum};
 use console::style;
-use directories::ProjectDirs;
-use indicatif::{ProgressBar, ProgressStyle};
-use sdk::{
-    get_installed_sdk_version, get_latest_sdk_version, get_sdk_path, get_wasi_sysroot_path,
-    install_latest_sdk, remove_sdk_version,
+use deploy::{deploy_package, undeploy_package, DeployMode};
+use indicatif::HumanDuration;
+use inspect::inspect_module;
+use lock::{get_locked_sdk, lock_installed_sdk, LOCK_FILE_NAME};
+use metadata::get_crate_metadata;
+use new::{new_crate, CrateKind};
+use output::{
+    emit, install_progress, is_formatted_output, print_build_result, print_message,
+    set_formatted_output, CliBuildObserver, ErrorInfo, Event,
 };
+use package::write_package;
 use serde::Serialize;
-use wasm_opt::{Feature, OptimizationOptions, Pass};
-
-/// SDK info and download utility
-mod sdk;
-
-#[cfg(target_os = "windows")]
-const BUILT_INS_PATH: &str = ".\\lib\\wasm32-wasi\\libclang_rt.builtins-wasm32.a";
-#[cfg(not(target_os = "windows"))]
-const BUILT_INS_PATH: &str = "./lib/wasm32-wasi/libclang_rt.builtins-wasm32.a";
-
-#[cfg(target_os = "windows")]
-const WASI_PATH: &str = ".\\lib\\wasm32-wasi";
-#[cfg(not(target_os = "windows"))]
-const WASI_PATH: &str = "./lib/wasm32-wasi";
-
-/// A specific version of MSFS
-#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum, Serialize)]
-enum SimulatorVersion {
-    Msfs2020,
-    Msfs2024,
-}
+use serde_json::{json, Value};
+use smoke::smoke_test;
+use test::test_crate;
+use verify::verify_build;
+use watch::watch_crate;
+
+/// Deploying packages into the Community folder
+mod deploy;
+/// WASM module inspection
+mod inspect;
+/// Crate scaffolding
+mod new;
+/// Printing styled text or JSON events
+mod output;
+/// MSFS package manifest.json generation and validation
+mod package;
+/// Embedded WASM runtime with WASI and stubbed sim functions
+mod runtime;
+/// Running modules headlessly
+mod smoke;
+/// Running crate tests in the embedded runtime
+mod test;
+/// Verifying reproducible builds
+mod verify;
+/// Rebuilding crates on source changes
+mod watch;
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum CommandType {
@@ -50,6 +67,26 @@ enum CommandType {
     Build,
     /// Gets info on installed SDKs
     Info,
+    /// Locks a crate to the installed SDK for a specified MSFS version
+    Lock,
+    /// Lists every published SDK version for a specified MSFS version, or for both
+    ListRemote,
+    /// Writes the layout.json and creates or validates the manifest.json of a package
+    Package,
+    /// Deploys a package into the Community folder of a sim
+    Deploy,
+    /// Removes a deployed package from the Community folder of a sim
+    Undeploy,
+    /// Creates a new gauge or standalone module crate
+    New,
+    /// Runs a built module in an embedded runtime with stubbed sim functions to catch crashes
+    Smoke,
+    /// Runs a crate's tests compiled for a specified MSFS version in an embedded runtime with stubbed sim functions
+    Test,
+    /// Shows how a WASM module was built and what it imports, exports and uses
+    Inspect,
+    /// Rebuilds a crate reproducibly and checks the result matches a given module
+    VerifyBuild,
 }
 
 #[derive(Debug, Parser)]
@@ -57,35 +94,147 @@ struct Args {
     /// The command to run
     #[arg(value_enum)]
     command: CommandType,
-    /// The version of MSFS to run for. This is optional if the command type is info
+    /// The version of MSFS to run for. This is optional if the command type is info or list-remote
     #[arg(value_enum, required_if_eq_any([
         ("command", "install"),
         ("command", "remove"),
         ("command", "update"),
+        ("command", "lock"),
         ("command", "build"),
+        ("command", "test"),
+        ("command", "verify-build"),
     ]))]
     msfs_version: Option<SimulatorVersion>,
-    /// The path to the crate to build. This is only required for the build command type
+    /// The path to the crate to build, or the WASM file to run or inspect for the smoke and inspect command types. This is only required for the build, package, smoke, test, inspect and verify-build command types. The lock command type locks this crate, or the current directory
     #[arg(short, required_if_eq_any([
         ("command", "build"),
+        ("command", "package"),
+        ("command", "smoke"),
+        ("command", "test"),
+        ("command", "inspect"),
+        ("command", "verify-build"),
     ]))]
     in_folder: Option<String>,
-    /// The full path (including filename) to output the compiled WASM file. This is only required for the build command type
+    /// The full path (including filename) to output the compiled WASM file. This or the package folder is required for the build command type
+    #[arg(short, conflicts_with = "package_folder")]
+    out_wasm: Option<String>,
+    /// The root of an MSFS package. For the build command type, the compiled WASM file is placed at the module path declared in `[package.metadata.msfs]`. This is required for the package, deploy and undeploy command types
     #[arg(short, required_if_eq_any([
-        ("command", "build"),
+        ("command", "package"),
+        ("command", "deploy"),
+        ("command", "undeploy"),
     ]))]
-    out_wasm: Option<String>,
-    /// Format the output as JSON to parse programmatically
+    package_folder: Option<String>,
+    /// The Community folder of the sim. This is only required for the deploy and undeploy command types
+    #[arg(short, env = "MSFS_COMMUNITY_FOLDER", required_if_eq_any([
+        ("command", "deploy"),
+        ("command", "undeploy"),
+    ]))]
+    community_folder: Option<String>,
+    /// Install the SDK version locked in the crate's msfs.lock instead of the latest one. The crate is read from the in folder, or the current directory. This is only used by the install command type
+    #[arg(long)]
+    locked: bool,
+    /// Another download of the release to install alongside the core SDK, in the form TITLE[@ROOT][=FOLDER]. FOLDER only extracts a folder of its SDK files, and ROOT is the SDK folder of the installer if it's not the same as the core SDK's. This is only used by the install command type
+    #[arg(long = "component", value_parser = parse_component)]
+    components: Vec<SdkComponent>,
+    /// Only extract the WASI sysroot and headers of the SDK, which is all builds need. Installing again without it installs the rest. This is only used by the install command type
+    #[arg(long)]
+    minimal: bool,
+    /// Only report installed SDKs, without checking the latest versions online. This is only used by the info command type
+    #[arg(long)]
+    offline: bool,
+    /// Copy the package into the Community folder instead of linking it. This is only used by the deploy command type
+    #[arg(long)]
+    copy: bool,
+    /// The name of a gauge crate to create. This or the module name is required for the new command type
+    #[arg(long, conflicts_with = "module")]
+    gauge: Option<String>,
+    /// The name of a standalone module crate to create. This or the gauge name is required for the new command type
+    #[arg(long)]
+    module: Option<String>,
+    /// Also create a package skeleton in the new crate. This is only used by the new command type
+    #[arg(long)]
+    with_package: bool,
+    /// The number of frames to draw gauges for. This is only used by the smoke command type
+    #[arg(short = 'n', default_value_t = 60)]
+    frames: u32,
+    /// A value returned by a stubbed sim function, in the form NAME=VALUE. Functions without a value return zero. This is only used by the smoke and test command types
+    #[arg(long = "stub", value_parser = parse_stub)]
+    stubs: Vec<(String, f64)>,
+    /// Rebuild the crate whenever its sources change. This is only used by the build command type
+    #[arg(short)]
+    watch: bool,
+    /// Build so the same commit produces the same bytes on any machine: machine specific paths are remapped, only a whitelisted environment is passed to cargo and SOURCE_DATE_EPOCH is pinned. This is only used by the build command type
+    #[arg(long)]
+    reproducible: bool,
+    /// A previously built module to compare a reproducible rebuild against. This is only required for the verify-build command type
+    #[arg(long, required_if_eq("command", "verify-build"))]
+    artifact: Option<String>,
+    /// Format the output as newline-delimited JSON events (messages, progress, diagnostics, artifacts and a final result) to parse programmatically
     #[arg(short)]
     formatted_output: bool,
 }
 
+/// The latest SDK version of a sim, as far as we know
+#[derive(Debug, Serialize)]
+struct LatestVersion {
+    /// The version, or None if it couldn't be fetched and wasn't cached
+    version: Option<String>,
+    /// When the version was cached, in seconds since the Unix epoch, if it couldn't be fetched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cached_at: Option<u64>,
+}
+
+/// Gets the latest SDK version of a given sim, falling back to the cached manifest if it can't be fetched
+///
+/// * `sdk_manager` - The SDK manager
+/// * `simulator_version` - The simulator version to get for
+fn get_latest_version(
+    sdk_manager: &SdkManager,
+    simulator_version: SimulatorVersion,
+) -> Result<LatestVersion> {
+    let error = match sdk_manager.latest_version(simulator_version) {
+        Ok(version) => {
+            return Ok(LatestVersion {
+                version: Some(version),
+                cached_at: None,
+            })
+        }
+        Err(e) => e,
+    };
+    print_warning(&format!(
+        "couldn't check the latest {simulator_version:?} SDK version: {:#}",
+        anyhow!(error)
+    ));
+
+    let Some(cached) = sdk_manager.cached_manifest(simulator_version)? else {
+        return Ok(LatestVersion {
+            version: None,
+            cached_at: None,
+        });
+    };
+    Ok(LatestVersion {
+        version: cached
+            .manifest
+            .latest_release()
+            .and_then(get_release_number)
+            .ok(),
+        cached_at: Some(cached.fetched_at),
+    })
+}
+
 /// Formats a string containing the installed SDK version of a given sim
 ///
-/// Example: `MSFS2024 SDK version X.X.X is installed` or `MSFS 2024 SDK is not installed`
+/// Example: `MSFS2024 SDK version X.X.X is installed, latest available version is Y.Y.Y` or `MSFS 2024 SDK is not installed`
 ///
 /// * `simulator_version` - The simulator version to format for
-fn format_version_string(simulator_version: SimulatorVersion) -> Result<String> {
+/// * `installed` - The installed SDK version
+/// * `latest` - The latest SDK version, or None if it wasn't checked
+fn format_version_string(
+    simulator_version: SimulatorVersion,
+    installed: Option<&str>,
+    latest: Option<&LatestVersion>,
+) -> String {
     let root_string = format!(
         "MSFS {} SDK",
         if simulator_version == SimulatorVersion::Msfs2020 {
@@ -95,242 +244,311 @@ fn format_version_string(simulator_version: SimulatorVersion) -> Result<String>
         }
     );
 
-    if let Some(installed_version) = get_installed_sdk_version(simulator_version)? {
-        Ok(format!(
-            "{} version {} is installed, latest available version is {}",
+    let Some(installed_version) = installed else {
+        return format!("{} is not installed", root_string);
+    };
+    let Some(latest) = latest else {
+        return format!(
+            "{} version {} is installed",
             root_string,
-            style(installed_version.trim_end()).bold(),
-            style(get_latest_sdk_version(simulator_version)?).bold()
-        ))
-    } else {
-        Ok(format!("{} is not installed", root_string))
+            style(installed_version).bold()
+        );
+    };
+
+    let latest_string = match (&latest.version, latest.cached_at) {
+        (None, _) => "unknown".to_string(),
+        (Some(version), None) => style(version).bold().to_string(),
+        (Some(version), Some(cached_at)) => {
+            let age = SystemTime::now()
+                .duration_since(UNIX_EPOCH + Duration::from_secs(cached_at))
+                .unwrap_or_default();
+            format!(
+                "{} (as of {} ago)",
+                style(version).bold(),
+                HumanDuration(age)
+            )
+        }
+    };
+    format!(
+        "{} version {} is installed, latest available version is {}",
+        root_string,
+        style(installed_version).bold(),
+        latest_string
+    )
+}
+
+/// Prints published SDK releases as a table, marking the installed ones
+///
+/// * `releases` - The releases to print
+fn print_remote_releases(releases: &[RemoteSdk]) {
+    let version_width = releases
+        .iter()
+        .map(|r| r.version.len())
+        .chain(["VERSION".len()])
+        .max()
+        .unwrap_or_default();
+
+    println!(
+        "{:<10} {:<version_width$} {:<9} DOWNLOAD URL",
+        "SIMULATOR", "VERSION", "INSTALLED"
+    );
+    for release in releases {
+        let url = release
+            .downloads
+            .get(CORE_INSTALLER_KEY)
+            .map_or("-", |u| u.as_str());
+        // Pad before styling, since escape codes would count towards the width
+        let installed = format!("{:<9}", if release.installed { "*" } else { "" });
+        println!(
+            "{:<10} {:<version_width$} {} {url}",
+            format!("{:?}", release.simulator),
+            release.version,
+            style(installed).green(),
+        );
+    }
+}
+
+/// Parses a stub value argument in the form NAME=VALUE
+///
+/// * `argument` - The argument to parse
+fn parse_stub(argument: &str) -> Result<(String, f64)> {
+    let (name, value) = argument
+        .split_once('=')
+        .context("stub values must be in the form NAME=VALUE")?;
+
+    Ok((name.to_string(), value.parse()?))
+}
+
+/// Parses a component argument in the form TITLE[@ROOT][=FOLDER]
+///
+/// * `argument` - The argument to parse
+fn parse_component(argument: &str) -> Result<SdkComponent> {
+    let (title, prefix) = argument.split_once('=').unwrap_or((argument, ""));
+    let (download, root) = match title.rsplit_once('@') {
+        Some((download, root)) => (download, Some(root)),
+        None => (title, None),
+    };
+    if download.is_empty() || root.is_some_and(str::is_empty) {
+        return Err(anyhow!(
+            "components must be in the form TITLE[@ROOT][=FOLDER]"
+        ));
     }
+
+    let component = SdkComponent::new(download, prefix);
+    Ok(match root {
+        Some(root) => component.root(root),
+        None => component,
+    })
 }
 
-/// Gets the directory that can be used for data
-fn get_data_dir() -> Result<PathBuf> {
-    Ok(ProjectDirs::from("", "", "cargo-msfs")
-        .context("could not get project dir")?
-        .data_dir()
-        .to_path_buf())
+/// Gets the manager of the SDKs installed in the user's data directory
+fn get_sdk_manager() -> Result<SdkManager> {
+    Ok(SdkManager::from_default_data_dir()?.with_warnings(print_warning))
 }
 
 /// Logs info
 fn print_info(message: &str) {
-    println!("{} {}", style("[INFO]").cyan(), message);
+    print_message("info", style("[INFO]").cyan(), message);
 }
 
 /// Logs success
 fn print_success(message: &str) {
-    println!("{} {}", style("[SUCCESS]").green(), message);
+    print_message("success", style("[SUCCESS]").green(), message);
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+/// Logs a warning
+fn print_warning(message: &str) {
+    print_message("warning", style("[WARNING]").yellow(), message);
+}
 
+/// Logs an error
+fn print_error(message: &str) {
+    print_message("error", style("[ERROR]").red(), message);
+}
+
+/// Runs a command
+///
+/// * `args` - The parsed arguments
+/// * `command_output` - Set to the command specific output reported in the result event when formatting output as JSON, e.g. the versions for the info command
+fn run(args: Args, command_output: &mut Option<Value>) -> Result<()> {
     match args.command {
         CommandType::Install => {
             let sim_version = args.msfs_version.unwrap();
-            let installed_version = get_installed_sdk_version(sim_version)?;
-            if installed_version.is_some() {
-                print_info("SDK for simulator version is already installed. To update it, run with the update command");
+            let sdk_manager = get_sdk_manager()?;
+            let installed_version = sdk_manager.installed_version(sim_version)?;
+            let locked_sdk = if args.locked {
+                let in_folder = args.in_folder.unwrap_or(".".to_string());
+                Some(get_locked_sdk(&in_folder, sim_version)?.ok_or(anyhow!(
+                    "{LOCK_FILE_NAME} doesn't lock an SDK version for {sim_version:?}"
+                ))?)
+            } else {
+                None
+            };
+
+            // Whether the wanted SDK version is installed, so there's nothing to download unless only part of it is
+            let wanted_installed = match &locked_sdk {
+                Some(locked_sdk) => {
+                    installed_version
+                        .as_deref()
+                        .is_some_and(|v| is_same_sdk_version(v, &locked_sdk.version))
+                        && (locked_sdk.installer_sha256.is_none()
+                            || locked_sdk.installer_sha256
+                                == sdk_manager.installed_hash(sim_version)?)
+                }
+                None => installed_version.is_some(),
+            };
+            if wanted_installed {
+                // Only install what's missing from the installed SDK
+                let installed = sdk_manager.installed(sim_version)?;
+                let incomplete = !args.minimal && installed.as_ref().is_some_and(|i| i.minimal);
+                let missing_components = args.components.iter().any(|component| {
+                    installed
+                        .iter()
+                        .flat_map(|i| &i.components)
+                        .all(|c| &c.component != component)
+                });
+                if incomplete {
+                    print_info("Downloading and installing the rest of the SDK...");
+                    sdk_manager.complete(sim_version, install_progress().as_mut())?;
+                    print_success("SDK installed");
+                }
+                if missing_components {
+                    print_info("Downloading and installing SDK components...");
+                    let added = sdk_manager.add_components(
+                        sim_version,
+                        &args.components,
+                        install_progress().as_mut(),
+                    )?;
+                    print_success(&format!("Installed {} SDK components", added.len()));
+                }
+                if !incomplete && !missing_components {
+                    if locked_sdk.is_some() {
+                        print_info("Locked SDK version is already installed");
+                    } else {
+                        print_info("SDK for simulator version is already installed. To update it, run with the update command");
+                    }
+                }
+                *command_output = Some(json!({ "sdk": sdk_manager.installed(sim_version)? }));
                 return Ok(());
             }
 
+            let release = match &locked_sdk {
+                Some(locked_sdk) => sdk_manager.find_release(sim_version, &locked_sdk.version)?,
+                None => sdk_manager.latest_release(sim_version)?,
+            };
+
             print_info("Downloading and installing SDK...");
-            // Create the progress bar. Since we won't know the full length until the callback, initialize with 0
-            let progress_bar = ProgressBar::new(0);
-            progress_bar.set_style(
-                ProgressStyle::with_template(
-                    "{spinner:.green} [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} (ETA {eta})",
-                )
-                .unwrap()
-                .progress_chars("#>-"),
-            );
-            progress_bar.enable_steady_tick(Duration::from_millis(100));
-            install_latest_sdk(
+            // Installing another version replaces the installed SDK, so keep its components like an update does
+            let mut components = sdk_manager.installed_options(sim_version)?.components;
+            for component in args.components {
+                if !components.contains(&component) {
+                    components.push(component);
+                }
+            }
+            // The same version can be re-published, so make sure we get the exact installer that was locked
+            let options = InstallOptions {
+                components,
+                minimal: args.minimal,
+                installer_sha256: locked_sdk.and_then(|l| l.installer_sha256),
+            };
+            sdk_manager.install_release(
                 sim_version,
-                Some(|downloaded, total| {
-                    if progress_bar.length() != Some(total) {
-                        progress_bar.set_length(total);
-                    }
-
-                    progress_bar.set_position(downloaded);
-                }),
+                &release,
+                &options,
+                install_progress().as_mut(),
             )?;
             print_success("SDK installed");
+            *command_output = Some(json!({ "sdk": sdk_manager.installed(sim_version)? }));
+        }
+        CommandType::Lock => {
+            let sim_version = args.msfs_version.unwrap();
+            let in_folder = args.in_folder.unwrap_or(".".to_string());
+            let locked = lock_installed_sdk(&in_folder, sim_version, &get_sdk_manager()?)?;
+            print_success(&format!(
+                "Locked {sim_version:?} SDK version {} in {LOCK_FILE_NAME}",
+                locked.version
+            ));
+            *command_output = Some(json!({ "sdk": locked }));
         }
         CommandType::Remove => {
             let sim_version = args.msfs_version.unwrap();
-            if get_sdk_path(sim_version)?.exists() {
-                remove_sdk_version(sim_version)?;
+            let removed = get_sdk_manager()?.remove(sim_version)?;
+            if removed {
                 print_success("SDK deleted");
             } else {
                 print_info("SDK is not installed, nothing to remove");
             }
+            *command_output = Some(json!({ "removed": removed }));
         }
         CommandType::Update => {
             let sim_version = args.msfs_version.unwrap();
-            let latest_release = get_latest_sdk_version(sim_version)?;
-            let installed_version = get_installed_sdk_version(sim_version)?;
-            if installed_version == Some(latest_release) {
-                print_info("Latest SDK is already installed");
-                return Ok(());
-            } else if installed_version.is_none() {
+            let sdk_manager = get_sdk_manager()?;
+            if sdk_manager.installed_version(sim_version)?.is_none() {
                 print_info("SDK is not installed. To install it, run with the install command");
                 return Ok(());
             }
 
-            print_info("Downloading and installing SDK...");
-            // Create the progress bar. Since we won't know the full length until the callback, initialize with 0
-            let progress_bar = ProgressBar::new(0);
-            progress_bar.set_style(
-                ProgressStyle::with_template(
-                    "{spinner:.green} [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} (ETA {eta})",
-                )
-                .unwrap()
-                .progress_chars("#>-"),
-            );
-            progress_bar.enable_steady_tick(Duration::from_millis(100));
-            install_latest_sdk(
-                sim_version,
-                Some(|downloaded, total| {
-                    if progress_bar.length() != Some(total) {
-                        progress_bar.set_length(total);
-                    }
-
-                    progress_bar.set_position(downloaded);
-                }),
-            )?;
-            print_success("SDK updated");
+            print_info("Checking for SDK updates...");
+            let updated = sdk_manager.update(sim_version, install_progress().as_mut())?;
+            if updated {
+                print_success("SDK updated");
+            } else {
+                print_info("Latest SDK is already installed");
+            }
+            *command_output = Some(json!({
+                "updated": updated,
+                "sdk": sdk_manager.installed(sim_version)?,
+            }));
         }
         CommandType::Build => {
             let sim_version = args.msfs_version.unwrap();
-
-            // Assure we downloaded the SDK
-            if get_installed_sdk_version(sim_version)?.is_none() {
-                return Err(anyhow!("SDK not installed"));
-            }
-
-            // Locate SDK wasi-sysroot
-            let sdk_path = get_sdk_path(sim_version)?;
-            let wasi_sysroot_path = get_wasi_sysroot_path(sim_version)?;
-            // Construct the build flags
-            let flags = [
-                "-Cstrip=symbols",
-                "-Clto",
-                "-Ctarget-feature=-crt-static,+bulk-memory",
-                "-Clink-self-contained=no",
-                "-Clink-arg=-l",
-                "-Clink-arg=c",
-                &format!(
-                    "-Clink-arg={}",
-                    wasi_sysroot_path.join(BUILT_INS_PATH).to_string_lossy()
-                ),
-                "-Clink-arg=-L",
-                &format!(
-                    "-Clink-arg={}",
-                    wasi_sysroot_path.join(WASI_PATH).to_string_lossy()
-                ),
-                "-Clink-arg=--export-table",
-                "-Clink-arg=--allow-undefined",
-                "-Clink-arg=--export-dynamic",
-                "-Clink-arg=--export=__wasm_call_ctors",
-                "-Clink-arg=--export=malloc",
-                "-Clink-arg=--export=free",
-                "-Clink-arg=--export=mark_decommit_pages",
-                "-Clink-arg=--export=mallinfo",
-                "-Clink-arg=--export=mchunkit_begin",
-                "-Clink-arg=--export=mchunkit_next",
-                "-Clink-arg=--export=get_pages_state",
-            ];
-            // Run build, capture output
-            let command = Command::new("cargo")
-                .args([
-                    "build",
-                    "--release",
-                    "--target",
-                    "wasm32-wasip1",
-                    "--message-format=json",
-                ])
-                .env("WASI_SYSROOT", wasi_sysroot_path.as_os_str())
-                .env("MSFS_SDK", sdk_path)
-                .env("RUSTFLAGS", flags.join(" "))
-                .env(
-                    "CFLAGS",
-                    format!("--sysroot={}", wasi_sysroot_path.to_string_lossy()),
-                )
-                .envs(std::env::vars()) // Inherit current environment variables
-                .current_dir(args.in_folder.unwrap().replace("\\", "/"))
-                .stdout(Stdio::piped())
-                .spawn()?
-                .wait_with_output()?;
-
-            // Map the JSON stdout to structures
-            let messages = Message::parse_stream(Cursor::new(command.stdout))
-                .map(|x| x.unwrap())
-                .collect::<Vec<_>>();
-
-            // Ensure build finished and did so successfully
-            let Some(Message::BuildFinished(data)) = messages.last() else {
-                return Err(anyhow!("build didn't finish"));
-            };
-            if !data.success {
-                // Print out the compiler messages to guide user on what went wrong
-                let compiler_messages = messages.iter().filter_map(|m| {
-                    if let Message::CompilerMessage(compiler_message) = m {
-                        Some(compiler_message)
-                    } else {
-                        None
-                    }
-                });
-
-                for compiler_message in compiler_messages {
-                    if let Some(message) = &compiler_message.message.rendered {
-                        println!("{message}");
-                    }
+            let in_folder = args.in_folder.unwrap();
+            let output = match (args.out_wasm, args.package_folder) {
+                (Some(out_wasm), _) => BuildOutput::Wasm(out_wasm),
+                (None, Some(package_folder)) => {
+                    BuildOutput::Package(PathBuf::from(package_folder.replace("\\", "/")))
                 }
-                return Err(anyhow!("build did not finish successfully"));
-            }
+                (None, None) => {
+                    return Err(anyhow!(
+                        "either an output WASM path or a package folder is required to build"
+                    ))
+                }
+            };
 
-            // Find the output artifacts
-            let out_artifact = messages
-                .iter()
-                .filter_map(|x| {
-                    if let Message::CompilerArtifact(data) = x {
-                        Some(data)
-                    } else {
-                        None
-                    }
-                })
-                .last()
-                .ok_or(anyhow!("couldn't get out artifact"))?;
+            let plan =
+                BuildPlan::new(sim_version, in_folder, output).reproducible(args.reproducible);
+            let builder = Builder::new(get_sdk_manager()?);
 
-            if out_artifact.filenames.len() > 1 {
-                return Err(anyhow!(
-                    "more than one file outputted for artifact, unsure how to proceed"
-                ));
+            if args.watch {
+                watch_crate(&builder, &plan)?;
+            } else {
+                let result = builder.build(&plan, &mut CliBuildObserver)?;
+                print_build_result(&result);
+                if let BuildOutput::Package(_) = plan.output {
+                    print_success(&format!(
+                        "Module written to {} and layout.json updated",
+                        result.module_path().display()
+                    ));
+                }
+                *command_output = Some(serde_json::to_value(&result)?);
             }
-
-            // Run wasm-opt
-            let path = out_artifact
-                .filenames
-                .get(0)
-                .ok_or(anyhow!("no filenames"))?;
-
-            OptimizationOptions::new_opt_level_1()
-                .add_pass(Pass::SignextLowering)
-                .enable_feature(Feature::BulkMemory)
-                .run(path, args.out_wasm.unwrap().replace("\\", "/"))?;
         }
 
         CommandType::Info => {
             #[derive(Serialize)]
             struct InstalledVersion {
                 sim: SimulatorVersion,
-                up_to_date: bool,
+                /// Whether the latest version is installed, or None if the latest version is unknown
+                up_to_date: Option<bool>,
                 installed: Option<String>,
-                latest: String,
+                latest: Option<String>,
+                /// When the latest version was cached, if it couldn't be fetched
+                #[serde(skip_serializing_if = "Option::is_none")]
+                latest_cached_at: Option<u64>,
+                /// The components installed alongside the core SDK
+                components: Vec<InstalledComponent>,
+                /// Whether only what builds need is installed
+                minimal: bool,
             }
 
             #[derive(Serialize)]
@@ -340,35 +558,191 @@ fn main() -> Result<()> {
 
             // List of supported simulators
             let simulators = [SimulatorVersion::Msfs2020, SimulatorVersion::Msfs2024];
+            let sdk_manager = get_sdk_manager()?;
 
-            if args.formatted_output {
-                let mut versions = vec![];
-
-                for &sim in &simulators {
-                    // Process only if no version is specified or if the current one is requested.
-                    if args.msfs_version.is_none() || args.msfs_version == Some(sim) {
-                        let installed = get_installed_sdk_version(sim)?;
-                        let latest = get_latest_sdk_version(sim)?;
-                        versions.push(InstalledVersion {
-                            sim,
-                            up_to_date: installed == Some(latest.clone()),
-                            installed,
-                            latest,
-                        });
-                    }
+            let mut versions = vec![];
+            for &sim in &simulators {
+                // Process only if no version is specified or if the current one is requested.
+                if args.msfs_version.is_some() && args.msfs_version != Some(sim) {
+                    continue;
+                }
+
+                let installed = sdk_manager.installed_version(sim)?;
+                // There's nothing to compare against if the SDK isn't installed, unless the output is parsed
+                let latest = if args.offline || (installed.is_none() && !args.formatted_output) {
+                    None
+                } else {
+                    Some(get_latest_version(&sdk_manager, sim)?)
+                };
+
+                print_info(&format_version_string(
+                    sim,
+                    installed.as_deref(),
+                    latest.as_ref(),
+                ));
+                let (components, minimal) = sdk_manager
+                    .installed(sim)?
+                    .map(|i| (i.components, i.minimal))
+                    .unwrap_or_default();
+                if minimal {
+                    print_info("  with only what builds need. To install the rest, run with the install command");
+                }
+                for installed_component in &components {
+                    let component = &installed_component.component;
+                    // Show the folder extracted from the installer, if it isn't everything in the core SDK's folder
+                    let folder = match (&component.root, component.prefix.as_str()) {
+                        (None, "") => String::new(),
+                        (None, prefix) => format!(" ({prefix})"),
+                        (Some(root), "") => format!(" ({root})"),
+                        (Some(root), prefix) => format!(" ({root}/{prefix})"),
+                    };
+                    print_info(&format!("  with {}{folder}", component.download));
+                }
+                let latest_version = latest.as_ref().and_then(|l| l.version.clone());
+                versions.push(InstalledVersion {
+                    sim,
+                    up_to_date: latest_version.as_ref().map(|l| {
+                        installed
+                            .as_deref()
+                            .is_some_and(|i| is_same_sdk_version(i, l))
+                    }),
+                    installed,
+                    latest: latest_version,
+                    latest_cached_at: latest.and_then(|l| l.cached_at),
+                    components,
+                    minimal,
+                });
+            }
+
+            *command_output = Some(serde_json::to_value(InstallInfo { versions })?);
+        }
+        CommandType::ListRemote => {
+            #[derive(Serialize)]
+            struct RemoteList {
+                releases: Vec<RemoteSdk>,
+            }
+
+            let sdk_manager = get_sdk_manager()?;
+            let mut releases = vec![];
+            for sim in [SimulatorVersion::Msfs2020, SimulatorVersion::Msfs2024] {
+                if args.msfs_version.is_none() || args.msfs_version == Some(sim) {
+                    releases.extend(sdk_manager.list_remote(sim)?);
                 }
+            }
 
-                let info = InstallInfo { versions };
-                println!("{}", serde_json::to_string(&info)?);
+            if args.formatted_output {
+                *command_output = Some(serde_json::to_value(RemoteList { releases })?);
             } else {
-                for &sim in &simulators {
-                    if args.msfs_version.is_none() || args.msfs_version == Some(sim) {
-                        print_info(&format_version_string(sim)?);
-                    }
+                print_remote_releases(&releases);
+            }
+        }
+        CommandType::Package => {
+            let (package, msfs_metadata) = get_crate_metadata(&args.in_folder.unwrap())?;
+            let package_folder = PathBuf::from(args.package_folder.unwrap().replace("\\", "/"));
+
+            write_package(&package_folder, &package, &msfs_metadata)?;
+            print_success("Package layout.json and manifest.json written");
+        }
+        CommandType::Deploy => {
+            let package_folder = PathBuf::from(args.package_folder.unwrap().replace("\\", "/"));
+            let community_folder = PathBuf::from(args.community_folder.unwrap().replace("\\", "/"));
+            let mode = if args.copy {
+                DeployMode::Copy
+            } else {
+                DeployMode::Symlink
+            };
+
+            let deployed_path = deploy_package(&package_folder, &community_folder, mode)?;
+            print_success(&format!("Package deployed to {}", deployed_path.display()));
+        }
+        CommandType::Undeploy => {
+            let package_folder = PathBuf::from(args.package_folder.unwrap().replace("\\", "/"));
+            let community_folder = PathBuf::from(args.community_folder.unwrap().replace("\\", "/"));
+
+            if undeploy_package(&package_folder, &community_folder)? {
+                print_success("Package removed from the Community folder");
+            } else {
+                print_info("Package is not deployed, nothing to remove");
+            }
+        }
+        CommandType::New => {
+            let (name, kind) = match (args.gauge, args.module) {
+                (Some(name), _) => (name, CrateKind::Gauge),
+                (None, Some(name)) => (name, CrateKind::Module),
+                (None, None) => {
+                    return Err(anyhow!(
+                        "either a gauge or a module name is required to create a crate"
+                    ))
                 }
+            };
+
+            let crate_folder = new_crate(&name, kind, args.with_package)?;
+            print_success(&format!("Created crate in {}", crate_folder.display()));
+        }
+        CommandType::Smoke => {
+            let wasm_path = PathBuf::from(args.in_folder.unwrap().replace("\\", "/"));
+            smoke_test(&wasm_path, args.frames, args.stubs.into_iter().collect())?;
+        }
+        CommandType::Test => {
+            let report = test_crate(
+                args.msfs_version.unwrap(),
+                &args.in_folder.unwrap(),
+                args.stubs.into_iter().collect(),
+            )?;
+            *command_output = Some(serde_json::to_value(&report)?);
+
+            if report.failed > 0 {
+                return Err(anyhow!("{} test(s) failed", report.failed));
+            }
+        }
+        CommandType::Inspect => {
+            let wasm_path = PathBuf::from(args.in_folder.unwrap().replace("\\", "/"));
+            let report = inspect_module(&fs::read(&wasm_path)?)?;
+
+            if args.formatted_output {
+                *command_output = Some(serde_json::to_value(&report)?);
+            } else {
+                report.print();
             }
         }
+        CommandType::VerifyBuild => {
+            let artifact = PathBuf::from(args.artifact.unwrap().replace("\\", "/"));
+            verify_build(
+                args.msfs_version.unwrap(),
+                &args.in_folder.unwrap(),
+                &artifact,
+            )?;
+        }
     }
 
     Ok(())
 }
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    set_formatted_output(args.formatted_output);
+
+    let mut command_output = None;
+    let result = run(args, &mut command_output);
+    let kind = result.as_ref().err().map(ErrorKind::of);
+
+    if is_formatted_output() {
+        emit(&Event::Result {
+            success: result.is_ok(),
+            output: command_output.as_ref(),
+            error: result
+                .as_ref()
+                .err()
+                .zip(kind)
+                .map(|(error, kind)| ErrorInfo {
+                    kind,
+                    exit_code: kind.exit_code(),
+                    message: format!("{error:#}"),
+                }),
+        });
+    } else if let Err(error) = &result {
+        print_error(&format!("{error:#}"));
+    }
+
+    kind.map_or(ExitCode::SUCCESS, |kind| ExitCode::from(kind.exit_code()))
+}
@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use cargo_metadata::{MetadataCommand, Package};
+use serde::Deserialize;
+
+// The key under `[package.metadata]` holding our configuration
+const METADATA_KEY: &str = "msfs";
+
+// The folder in a package that standalone modules are placed in when no module path is declared
+const DEFAULT_MODULE_FOLDER: &str = "modules";
+
+/// Configuration read from the `[package.metadata.msfs]` table of a crate's Cargo.toml
+#[derive(Debug, Default, Deserialize, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct MsfsMetadata {
+    /// The path of the built module relative to the package root, e.g. `SimObjects/Airplanes/MyPlane/panel/gauge.wasm`
+    pub module_path: Option<String>,
+    /// The package title written to `manifest.json`. Defaults to the crate name
+    pub title: Option<String>,
+    /// The package creator written to `manifest.json`. Defaults to the first crate author
+    pub creator: Option<String>,
+    /// The minimum sim version written to `manifest.json`
+    pub minimum_game_version: Option<String>,
+    /// The package content type written to `manifest.json`, e.g. `INSTRUMENTS` or `MISC`
+    pub content_type: Option<String>,
+    /// The maximum size of the built module in bytes. Builds producing a bigger module fail
+    pub size_budget: Option<u64>,
+}
+
+impl MsfsMetadata {
+    /// Gets the path of the built module relative to the package root, falling back to `modules/<crate name>.wasm`
+    ///
+    /// * `package` - The package the metadata was read from
+    pub fn module_path(&self, package: &Package) -> String {
+        self.module_path
+            .clone()
+            .unwrap_or_else(|| format!("{}/{}.wasm", DEFAULT_MODULE_FOLDER, package.name))
+    }
+}
+
+/// Reads the Cargo package of a crate along with its `[package.metadata.msfs]` table
+///
+/// * `in_folder` - The path to the crate
+pub fn get_crate_metadata(in_folder: &str) -> Result<(Package, MsfsMetadata)> {
+    let metadata = MetadataCommand::new()
+        .current_dir(in_folder.replace("\\", "/"))
+        .no_deps()
+        .exec()?;
+    let package = metadata
+        .root_package()
+        .context("couldn't find a root package, virtual workspaces are not supported")?
+        .clone();
+
+    let msfs_metadata = match package.metadata.get(METADATA_KEY) {
+        Some(value) => serde_json::from_value(value.clone())
+            .context("couldn't parse [package.metadata.msfs]")?,
+        None => MsfsMetadata::default(),
+    };
+
+    Ok((package, msfs_metadata))
+}
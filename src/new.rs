@@ -0,0 +1,127 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{metadata::get_crate_metadata, package::write_package};
+
+// Templates for the generated files
+const CARGO_TOML_TEMPLATE: &str = include_str!("templates/Cargo.toml.template");
+const GAUGE_TEMPLATE: &str = include_str!("templates/gauge.rs.template");
+const MODULE_TEMPLATE: &str = include_str!("templates/module.rs.template");
+const GITIGNORE_TEMPLATE: &str = include_str!("templates/gitignore.template");
+
+// The folder within the crate that the package skeleton is created in
+const PACKAGE_FOLDER_NAME: &str = "package";
+
+/// The kind of crate to generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrateKind {
+    /// A gauge, exporting a gauge callback that is referenced from panel.cfg
+    Gauge,
+    /// A standalone module, exporting `module_init` and `module_deinit`
+    Module,
+}
+
+/// Ensures a crate name is usable both as a Cargo package name and as part of an exported symbol
+///
+/// * `name` - The crate name
+fn validate_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid_start = chars.next().is_some_and(|c| c.is_ascii_alphabetic());
+    let valid_rest = chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid_start && valid_rest {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "invalid crate name `{name}`, it must start with a letter and only contain letters, numbers, `-` and `_`"
+        ))
+    }
+}
+
+/// Gets the name used for the package creator, preferring the git user name
+///
+/// * `fallback` - The name to use when git isn't configured
+fn get_creator(fallback: &str) -> String {
+    Command::new("git")
+        .args(["config", "user.name"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or(fallback.to_string())
+}
+
+/// Writes a file, creating its parent directories
+///
+/// * `path` - The path of the file
+/// * `contents` - The contents of the file
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    fs::create_dir_all(path.parent().context("could not get parent")?)?;
+    fs::write(path, contents)?;
+
+    Ok(())
+}
+
+/// Generates a new crate set up for building MSFS modules. Returns the path of the crate
+///
+/// * `name` - The name of the crate, which is also the folder it's created in
+/// * `kind` - Whether to generate a gauge or a standalone module
+/// * `with_package` - Whether to also generate a package skeleton containing a manifest.json and layout.json
+pub fn new_crate(name: &str, kind: CrateKind, with_package: bool) -> Result<PathBuf> {
+    validate_name(name)?;
+
+    let crate_folder = PathBuf::from(name);
+    if crate_folder.exists() {
+        return Err(anyhow!("destination `{name}` already exists"));
+    }
+
+    let ident = name.replace('-', "_");
+    let (module_path, content_type, lib_template) = match kind {
+        CrateKind::Gauge => (
+            format!("SimObjects/Airplanes/{ident}/panel/{ident}.wasm"),
+            "INSTRUMENTS",
+            GAUGE_TEMPLATE,
+        ),
+        CrateKind::Module => (format!("modules/{ident}.wasm"), "MISC", MODULE_TEMPLATE),
+    };
+    // The creator ends up in a TOML string, so escape it
+    let creator = get_creator(name).replace('\\', "\\\\").replace('"', "\\\"");
+
+    write_file(
+        &crate_folder.join("Cargo.toml"),
+        &CARGO_TOML_TEMPLATE
+            .replace("{{name}}", name)
+            .replace("{{module_path}}", &module_path)
+            .replace("{{creator}}", &creator)
+            .replace("{{content_type}}", content_type),
+    )?;
+    write_file(
+        &crate_folder.join("src").join("lib.rs"),
+        &lib_template.replace("{{ident}}", &ident),
+    )?;
+    write_file(&crate_folder.join(".gitignore"), GITIGNORE_TEMPLATE)?;
+
+    if with_package {
+        let package_folder = crate_folder.join(PACKAGE_FOLDER_NAME);
+        // Create the folder the module will be built into, so the layout is complete once it's built
+        let module_folder = package_folder
+            .join(&module_path)
+            .parent()
+            .context("could not get parent")?
+            .to_path_buf();
+        fs::create_dir_all(module_folder)?;
+
+        let (package, msfs_metadata) =
+            get_crate_metadata(crate_folder.to_str().context("couldn't convert to str")?)?;
+        write_package(&package_folder, &package, &msfs_metadata)?;
+    }
+
+    Ok(crate_folder)
+}
@@ -0,0 +1,297 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use console::StyledObject;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    build::{Artifact, BuildObserver, BuildResult, BuildStage, Diagnostic, DiagnosticLevel},
+    print_error, print_info, print_warning,
+    report::format_report,
+    ErrorKind, InstallProgress,
+};
+
+// Whether output is formatted as newline-delimited JSON events instead of styled text
+static FORMATTED_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether output is formatted as newline-delimited JSON events instead of styled text
+///
+/// * `formatted_output` - Whether to format output as JSON
+pub fn set_formatted_output(formatted_output: bool) {
+    FORMATTED_OUTPUT.store(formatted_output, Ordering::Relaxed);
+}
+
+/// Gets whether output is formatted as newline-delimited JSON events instead of styled text
+pub fn is_formatted_output() -> bool {
+    FORMATTED_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// An error a command failed with, as reported in its result event
+#[derive(Debug, Serialize)]
+pub struct ErrorInfo {
+    pub kind: ErrorKind,
+    pub exit_code: u8,
+    pub message: String,
+}
+
+/// The counts of a test suite, as libtest reports them when it starts and finishes
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum SuiteCounts {
+    Started {
+        test_count: usize,
+    },
+    Finished {
+        passed: usize,
+        failed: usize,
+        ignored: usize,
+        measured: usize,
+        filtered_out: usize,
+    },
+}
+
+/// One of libtest's JSON events, streamed by the test command as tests run
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LibtestEvent<'a> {
+    /// A test binary started or finished running its tests. The event is `started`, `ok` or `failed`
+    Suite {
+        event: &'static str,
+        #[serde(flatten)]
+        counts: SuiteCounts,
+    },
+    /// A test started or finished. The event is `started`, `ok`, `failed` or `ignored`
+    Test {
+        event: &'static str,
+        name: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stdout: Option<&'a str>,
+    },
+}
+
+/// A line of JSON output
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event<'a> {
+    /// A log message
+    Message { level: &'a str, message: &'a str },
+    /// A command moved on to a new stage, or made progress in it
+    Progress {
+        stage: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        current: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        total: Option<u64>,
+    },
+    /// A diagnostic emitted by a build
+    Diagnostic(&'a Diagnostic),
+    /// A file written by a build
+    Artifact(&'a Artifact),
+    /// A test binary or test started or finished, as libtest reports it
+    Test { libtest: &'a LibtestEvent<'a> },
+    /// The outcome of the command. This is always the last event
+    Result {
+        success: bool,
+        /// The command specific output, e.g. the versions for the info command
+        #[serde(flatten)]
+        output: Option<&'a Value>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<ErrorInfo>,
+    },
+}
+
+/// Prints an event as a line of JSON
+///
+/// * `event` - The event to print
+pub fn emit(event: &Event) {
+    println!(
+        "{}",
+        serde_json::to_string(event).expect("events always serialize")
+    );
+}
+
+impl LibtestEvent<'_> {
+    /// Prints the libtest event as a line of JSON, wrapped in a test event
+    pub fn emit(&self) {
+        emit(&Event::Test { libtest: self });
+    }
+}
+
+/// Logs a message prefixed with a styled tag, or as a message event when formatting output as JSON
+///
+/// * `level` - The level of the message, e.g. `info`
+/// * `tag` - The styled tag to prefix the message with
+/// * `message` - The message
+pub fn print_message(level: &str, tag: StyledObject<&str>, message: &str) {
+    if is_formatted_output() {
+        emit(&Event::Message { level, message });
+    } else if level == "error" {
+        eprintln!("{tag} {message}");
+    } else {
+        println!("{tag} {message}");
+    }
+}
+
+/// Prints build diagnostics as they are emitted, and the stages of the build when formatting output as JSON
+pub struct CliBuildObserver;
+
+impl BuildObserver for CliBuildObserver {
+    fn stage(&mut self, stage: BuildStage) {
+        if is_formatted_output() {
+            let stage = serde_json::to_value(stage).expect("stages always serialize");
+            emit(&Event::Progress {
+                stage: stage.as_str().unwrap_or_default(),
+                current: None,
+                total: None,
+            });
+        }
+    }
+
+    fn diagnostic(&mut self, diagnostic: &Diagnostic) {
+        if is_formatted_output() {
+            emit(&Event::Diagnostic(diagnostic));
+            return;
+        }
+
+        // Compiler messages are already formatted
+        if let Some(rendered) = &diagnostic.rendered {
+            println!("{rendered}");
+            return;
+        }
+
+        match diagnostic.level {
+            DiagnosticLevel::Error => print_error(&diagnostic.message),
+            DiagnosticLevel::Warning => print_warning(&diagnostic.message),
+            DiagnosticLevel::Note => print_info(&diagnostic.message),
+        }
+    }
+}
+
+/// Prints the size report of a build compared to the previous build if there was one, or its artifacts when formatting output as JSON
+///
+/// * `result` - The result of the build
+pub fn print_build_result(result: &BuildResult) {
+    if is_formatted_output() {
+        for artifact in &result.artifacts {
+            emit(&Event::Artifact(artifact));
+        }
+        return;
+    }
+
+    for line in format_report(&result.report, result.previous_report.as_ref()) {
+        print_info(&line);
+    }
+}
+
+/// Shows SDK install progress in a progress bar: bytes while downloading, files while extracting
+struct InstallProgressBar {
+    bar: ProgressBar,
+    /// The stage the bar is showing
+    stage: Option<&'static str>,
+}
+
+impl InstallProgressBar {
+    /// Switches the bar to a stage, restarting it if it was showing another one
+    ///
+    /// * `stage` - The stage
+    /// * `template` - The template of the bar for the stage
+    fn set_stage(&mut self, stage: &'static str, template: &str) {
+        if self.stage == Some(stage) {
+            return;
+        }
+        self.stage = Some(stage);
+
+        self.bar.set_style(
+            ProgressStyle::with_template(template)
+                .unwrap()
+                .progress_chars("#>-"),
+        );
+        self.bar.reset();
+    }
+
+    /// Updates the bar
+    ///
+    /// * `current` - The progress so far
+    /// * `total` - The total
+    fn update(&mut self, current: u64, total: u64) {
+        if self.bar.length() != Some(total) {
+            self.bar.set_length(total);
+        }
+
+        self.bar.set_position(current);
+    }
+}
+
+impl InstallProgress for InstallProgressBar {
+    fn download(&mut self, downloaded: u64, total: u64) {
+        self.set_stage(
+            "download",
+            "{spinner:.green} [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} (ETA {eta})",
+        );
+        self.update(downloaded, total);
+    }
+
+    fn extract(&mut self, extracted: u64, total: u64) {
+        self.set_stage(
+            "extract",
+            "{spinner:.green} [{wide_bar:.cyan/blue}] {pos}/{len} files (ETA {eta})",
+        );
+        self.update(extracted, total);
+    }
+}
+
+/// Reports SDK install progress as progress events, at most once per percent, or once per MiB of downloads if the size is unknown
+struct InstallProgressEvents {
+    last_step: Option<(&'static str, u64)>,
+}
+
+impl InstallProgressEvents {
+    /// Emits a progress event if the progress of a stage moved on a step
+    ///
+    /// * `stage` - The stage
+    /// * `current` - The progress so far
+    /// * `total` - The total, or 0 if it's unknown
+    /// * `unknown_step` - The size of a step if the total is unknown
+    fn report(&mut self, stage: &'static str, current: u64, total: u64, unknown_step: u64) {
+        let step = (current * 100)
+            .checked_div(total)
+            .unwrap_or(current / unknown_step);
+        if self.last_step == Some((stage, step)) {
+            return;
+        }
+        self.last_step = Some((stage, step));
+
+        emit(&Event::Progress {
+            stage,
+            current: Some(current),
+            total: Some(total),
+        });
+    }
+}
+
+impl InstallProgress for InstallProgressEvents {
+    fn download(&mut self, downloaded: u64, total: u64) {
+        self.report("download", downloaded, total, 1024 * 1024);
+    }
+
+    fn extract(&mut self, extracted: u64, total: u64) {
+        self.report("extract", extracted, total, 1);
+    }
+}
+
+/// Creates the reporter of SDK install progress: a progress bar, or progress events when formatting output as JSON
+pub fn install_progress() -> Box<dyn InstallProgress> {
+    if is_formatted_output() {
+        return Box::new(InstallProgressEvents { last_step: None });
+    }
+
+    // Since we won't know the full length until the first update, initialize with 0
+    let bar = ProgressBar::new(0);
+    bar.enable_steady_tick(Duration::from_millis(100));
+
+    Box::new(InstallProgressBar { bar, stage: None })
+}
@@ -0,0 +1,162 @@
+use std::{
+    fs::{self, File},
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+use cargo_metadata::Package;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    layout::{write_layout, MANIFEST_FILE_NAME},
+    metadata::MsfsMetadata,
+};
+
+// Values used for manifest fields that aren't declared in `[package.metadata.msfs]`
+const DEFAULT_CONTENT_TYPE: &str = "MISC";
+const DEFAULT_MINIMUM_GAME_VERSION: &str = "1.0.0";
+
+// Manifest fields that must be present and non-empty
+const REQUIRED_FIELDS: [&str; 5] = [
+    "title",
+    "creator",
+    "package_version",
+    "minimum_game_version",
+    "content_type",
+];
+
+/// Gets the manifest values derived from Cargo metadata, along with whether each one was explicitly declared
+///
+/// * `package` - The Cargo package
+/// * `msfs_metadata` - The `[package.metadata.msfs]` table of the package
+fn get_manifest_values(
+    package: &Package,
+    msfs_metadata: &MsfsMetadata,
+) -> Vec<(&'static str, String, bool)> {
+    // Authors are usually formatted as `Name <email>`, only the name is relevant
+    let author = package
+        .authors
+        .first()
+        .map(|a| a.split('<').next().unwrap_or(a).trim().to_string());
+
+    vec![
+        (
+            "title",
+            msfs_metadata.title.clone().unwrap_or(package.name.clone()),
+            msfs_metadata.title.is_some(),
+        ),
+        (
+            "creator",
+            msfs_metadata.creator.clone().or(author).unwrap_or_default(),
+            msfs_metadata.creator.is_some(),
+        ),
+        ("package_version", package.version.to_string(), true),
+        (
+            "minimum_game_version",
+            msfs_metadata
+                .minimum_game_version
+                .clone()
+                .unwrap_or(DEFAULT_MINIMUM_GAME_VERSION.to_string()),
+            msfs_metadata.minimum_game_version.is_some(),
+        ),
+        (
+            "content_type",
+            msfs_metadata
+                .content_type
+                .clone()
+                .unwrap_or(DEFAULT_CONTENT_TYPE.to_string()),
+            msfs_metadata.content_type.is_some(),
+        ),
+    ]
+}
+
+/// Checks an existing manifest for missing fields and values that disagree with Cargo metadata
+///
+/// * `manifest` - The parsed manifest
+/// * `values` - The values derived from Cargo metadata
+fn validate_manifest(manifest: &Map<String, Value>, values: &[(&str, String, bool)]) -> Result<()> {
+    let mut problems = vec![];
+
+    for field in REQUIRED_FIELDS {
+        match manifest.get(field).and_then(|v| v.as_str()) {
+            Some(value) if !value.is_empty() => {}
+            _ => problems.push(format!("`{field}` is missing or empty")),
+        }
+    }
+
+    // Only compare values that were explicitly declared, otherwise hand edited manifests would always be rejected
+    for (field, expected, declared) in values {
+        if !declared {
+            continue;
+        }
+        if let Some(actual) = manifest.get(*field).and_then(|v| v.as_str()) {
+            if actual != expected {
+                problems.push(format!(
+                    "`{field}` is `{actual}` but Cargo metadata declares `{expected}`"
+                ));
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "invalid {}:\n  {}",
+            MANIFEST_FILE_NAME,
+            problems.join("\n  ")
+        ))
+    }
+}
+
+/// Creates or validates the `manifest.json` of a package and regenerates its `layout.json`
+///
+/// * `package_folder` - The root of the package
+/// * `package` - The Cargo package to derive manifest values from
+/// * `msfs_metadata` - The `[package.metadata.msfs]` table of the package
+pub fn write_package(
+    package_folder: &Path,
+    package: &Package,
+    msfs_metadata: &MsfsMetadata,
+) -> Result<()> {
+    fs::create_dir_all(package_folder)?;
+    let package_size = write_layout(package_folder)?;
+
+    let manifest_path = package_folder.join(MANIFEST_FILE_NAME);
+    let values = get_manifest_values(package, msfs_metadata);
+
+    let mut manifest = if manifest_path.exists() {
+        let manifest = serde_json::from_reader::<_, Value>(File::open(&manifest_path)?)
+            .with_context(|| format!("couldn't parse {}", manifest_path.display()))?;
+        let Value::Object(manifest) = manifest else {
+            return Err(anyhow!("{} is not a JSON object", MANIFEST_FILE_NAME));
+        };
+        validate_manifest(&manifest, &values)?;
+        manifest
+    } else {
+        let mut manifest = Map::new();
+        manifest.insert("dependencies".to_string(), json!([]));
+        for (field, value, _) in &values {
+            manifest.insert(field.to_string(), Value::String(value.clone()));
+        }
+        manifest.insert("manufacturer".to_string(), json!(""));
+        manifest.insert(
+            "release_notes".to_string(),
+            json!({ "neutral": { "LastUpdate": "", "OlderHistory": "" } }),
+        );
+        // Catches values that couldn't be derived, such as a missing creator
+        validate_manifest(&manifest, &values)?;
+        manifest
+    };
+
+    // The sim expects the size as a zero padded string of 20 digits
+    manifest.insert(
+        "total_package_size".to_string(),
+        Value::String(format!("{:020}", package_size)),
+    );
+
+    let file = File::create(&manifest_path)?;
+    serde_json::to_writer_pretty(file, &manifest)?;
+
+    Ok(())
+}
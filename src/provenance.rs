@@ -0,0 +1,127 @@
+use std::{path::Path, process::Command};
+
+use anyhow::Result;
+use cargo_metadata::Package;
+use serde::{Deserialize, Serialize};
+use wasmparser::{Parser, Payload};
+
+use crate::SimulatorVersion;
+
+/// The name of the custom section holding the build info of modules
+pub const BUILD_INFO_SECTION: &str = "cargo-msfs.build-info";
+
+/// Where a module came from, embedded in every built module
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub crate_name: String,
+    pub crate_version: String,
+    /// The commit the crate was built from, if it's in a git repository
+    pub git_commit: Option<String>,
+    /// Whether the working tree had uncommitted changes
+    pub git_dirty: Option<bool>,
+    pub simulator: SimulatorVersion,
+    pub sdk_version: Option<String>,
+    pub rustc_version: Option<String>,
+    pub cargo_msfs_version: String,
+    /// The wasm-opt options the module was optimized with
+    pub wasm_opt: String,
+}
+
+/// Runs a command in a folder, returning its trimmed stdout if it succeeded
+///
+/// * `program` - The program to run
+/// * `args` - The arguments
+/// * `folder` - The working directory
+fn command_output(program: &str, args: &[&str], folder: &Path) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(folder)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl BuildInfo {
+    /// Collects the build info of a crate
+    ///
+    /// * `sim_version` - The simulator version the crate is built for
+    /// * `package` - The package of the crate
+    /// * `sdk_version` - The version of the SDK the crate is built with
+    /// * `wasm_opt` - The wasm-opt options the module is optimized with
+    pub fn collect(
+        sim_version: SimulatorVersion,
+        package: &Package,
+        sdk_version: Option<String>,
+        wasm_opt: &str,
+    ) -> Self {
+        let folder = package
+            .manifest_path
+            .parent()
+            .map(|p| p.as_std_path())
+            .unwrap_or(Path::new("."));
+        let git_commit = command_output("git", &["rev-parse", "HEAD"], folder);
+        let git_dirty = git_commit.as_ref().and_then(|_| {
+            command_output("git", &["status", "--porcelain"], folder).map(|s| !s.is_empty())
+        });
+
+        Self {
+            crate_name: package.name.clone(),
+            crate_version: package.version.to_string(),
+            git_commit,
+            git_dirty,
+            simulator: sim_version,
+            sdk_version,
+            // Run from the crate so its toolchain file is respected
+            rustc_version: command_output("rustc", &["--version"], folder),
+            cargo_msfs_version: env!("CARGO_PKG_VERSION").to_string(),
+            wasm_opt: wasm_opt.to_string(),
+        }
+    }
+
+    /// Formats the build info as lines of text
+    pub fn summary(&self) -> Vec<String> {
+        let unknown = || "unknown".to_string();
+        vec![
+            format!("Crate: {} {}", self.crate_name, self.crate_version),
+            format!(
+                "Git commit: {}{}",
+                self.git_commit.clone().unwrap_or_else(unknown),
+                if self.git_dirty == Some(true) {
+                    " (dirty)"
+                } else {
+                    ""
+                }
+            ),
+            format!("Simulator: {:?}", self.simulator),
+            format!(
+                "SDK version: {}",
+                self.sdk_version.clone().unwrap_or_else(unknown)
+            ),
+            format!(
+                "rustc: {}",
+                self.rustc_version.clone().unwrap_or_else(unknown)
+            ),
+            format!("cargo-msfs: {}", self.cargo_msfs_version),
+            format!("wasm-opt: {}", self.wasm_opt),
+        ]
+    }
+}
+
+/// Reads the build info embedded in a module, if there is any
+///
+/// * `wasm` - The WASM module
+pub fn read_build_info(wasm: &[u8]) -> Result<Option<BuildInfo>> {
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::CustomSection(section) = payload? {
+            if section.name() == BUILD_INFO_SECTION {
+                return Ok(Some(serde_json::from_slice(section.data())?));
+            }
+        }
+    }
+
+    Ok(None)
+}
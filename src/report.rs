@@ -0,0 +1,171 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::{FunctionSize, ModuleAnalysis, SectionSize};
+
+// How many of the largest functions are kept in a report
+const LARGEST_FUNCTION_COUNT: usize = 10;
+
+/// Size information about a built module
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildReport {
+    /// The size of the module produced by cargo
+    pub raw_size: u64,
+    /// The size of the module after wasm-opt and stripping
+    pub optimized_size: u64,
+    /// The sections of the optimized module
+    pub sections: Vec<SectionSize>,
+    /// The largest functions of the optimized module, biggest first
+    pub largest_functions: Vec<FunctionSize>,
+}
+
+impl BuildReport {
+    /// Creates a report of a build
+    ///
+    /// * `raw_size` - The size of the module produced by cargo
+    /// * `optimized` - The analysis of the final module
+    /// * `named` - The analysis of the final module before its name section was stripped
+    pub fn new(raw_size: u64, optimized: &ModuleAnalysis, named: &ModuleAnalysis) -> Self {
+        Self {
+            raw_size,
+            optimized_size: optimized.size,
+            sections: optimized.sections.clone(),
+            largest_functions: named.largest_functions(LARGEST_FUNCTION_COUNT),
+        }
+    }
+
+    /// Sums the sizes of the sections matching a predicate
+    ///
+    /// * `predicate` - Whether a section, given its name, is included
+    fn section_size(&self, predicate: impl Fn(&str) -> bool) -> u64 {
+        self.sections
+            .iter()
+            .filter(|s| predicate(&s.name))
+            .map(|s| s.size)
+            .sum()
+    }
+}
+
+/// Gets the path of the report saved next to a cargo artifact
+///
+/// * `artifact` - The path of the module produced by cargo
+fn get_report_path(artifact: &Path) -> PathBuf {
+    artifact.with_extension("msfs-report.json")
+}
+
+/// Reads the report of the previous build of an artifact, if there is one
+///
+/// * `artifact` - The path of the module produced by cargo
+pub fn read_previous_report(artifact: &Path) -> Option<BuildReport> {
+    let report = fs::read_to_string(get_report_path(artifact)).ok()?;
+    serde_json::from_str(&report).ok()
+}
+
+/// Saves the report of a build next to its cargo artifact, to compare the next build against
+///
+/// * `artifact` - The path of the module produced by cargo
+/// * `report` - The report to save
+pub fn save_report(artifact: &Path, report: &BuildReport) -> Result<()> {
+    fs::write(
+        get_report_path(artifact),
+        serde_json::to_string_pretty(report)?,
+    )?;
+
+    Ok(())
+}
+
+/// Formats a byte count in a human readable unit
+///
+/// Example: `512 B`, `12.3 KiB` or `1.5 MiB`
+///
+/// * `bytes` - The byte count
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1048576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
+
+/// Formats the difference between a size and its previous value
+///
+/// Example: ` (+1.2 KiB)`, ` (-512 B)`, or an empty string if the size didn't change
+///
+/// * `size` - The current size
+/// * `previous` - The previous size, if known
+fn format_delta(size: u64, previous: Option<u64>) -> String {
+    match previous {
+        Some(previous) if previous < size => format!(" (+{})", format_size(size - previous)),
+        Some(previous) if previous > size => format!(" (-{})", format_size(previous - size)),
+        _ => String::new(),
+    }
+}
+
+/// Groups a section into the categories shown in reports: code, data, custom or other
+///
+/// * `name` - The name of the section
+fn section_category(name: &str) -> &str {
+    match name {
+        "code" | "data" => name,
+        _ if name.starts_with("custom:") => "custom",
+        _ => "other",
+    }
+}
+
+/// Formats a build report as lines of text, comparing it to the previous build if there was one
+///
+/// * `report` - The report of the build
+/// * `previous` - The report of the previous build
+pub fn format_report(report: &BuildReport, previous: Option<&BuildReport>) -> Vec<String> {
+    let mut lines = vec![];
+    let reduction = if report.raw_size > 0 {
+        100.0 - report.optimized_size as f64 / report.raw_size as f64 * 100.0
+    } else {
+        0.0
+    };
+    lines.push(format!(
+        "Module size: {}{} ({} before wasm-opt, {reduction:.1}% smaller)",
+        format_size(report.optimized_size),
+        format_delta(report.optimized_size, previous.map(|p| p.optimized_size)),
+        format_size(report.raw_size),
+    ));
+
+    for category in ["code", "data", "custom", "other"] {
+        let predicate = |name: &str| section_category(name) == category;
+        let size = report.section_size(predicate);
+        lines.push(format!(
+            "  {category}: {}{}",
+            format_size(size),
+            format_delta(size, previous.map(|p| p.section_size(predicate)))
+        ));
+    }
+
+    if !report.largest_functions.is_empty() {
+        lines.push("Largest functions:".to_string());
+        for function in &report.largest_functions {
+            let name = function
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("func[{}]", function.index));
+            // Function indices shift between builds, so compare by name
+            let previous_size = previous.and_then(|p| {
+                p.largest_functions
+                    .iter()
+                    .find(|f| f.name.is_some() && f.name == function.name)
+                    .map(|f| f.size)
+            });
+            lines.push(format!(
+                "  {}{}  {name}",
+                format_size(function.size),
+                format_delta(function.size, previous_size)
+            ));
+        }
+    }
+
+    lines
+}
@@ -0,0 +1,83 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Result;
+use cargo_metadata::MetadataCommand;
+
+/// The environment variables passed to cargo in reproducible builds. Everything else is dropped so it can't leak into the build
+const ENV_WHITELIST: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USERPROFILE",
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+    "RUSTUP_TOOLCHAIN",
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "TEMP",
+    "TMP",
+    "TMPDIR",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "SOURCE_DATE_EPOCH",
+];
+// Prefixes of the environment variables passed to cargo in reproducible builds. Profile overrides are part of the build configuration the crate is linted against
+const ENV_PREFIX_WHITELIST: &[&str] = &["CARGO_PROFILE_RELEASE_"];
+
+/// Filters the current environment down to the variables reproducible builds are allowed to see
+pub fn get_whitelisted_env() -> Vec<(String, String)> {
+    env::vars()
+        // Windows environment variable names are case insensitive
+        .filter(|(key, _)| {
+            ENV_WHITELIST.iter().any(|w| w.eq_ignore_ascii_case(key))
+                || ENV_PREFIX_WHITELIST.iter().any(|p| {
+                    key.get(..p.len())
+                        .is_some_and(|k| k.eq_ignore_ascii_case(p))
+                })
+        })
+        .collect()
+}
+
+/// Gets the machine specific paths that end up in build outputs and what to replace them with
+///
+/// * `in_folder` - The path to the crate
+/// * `sdk_path` - The path of the installed SDK
+pub fn get_path_remaps(in_folder: &str, sdk_path: &Path) -> Result<Vec<(PathBuf, &'static str)>> {
+    let metadata = MetadataCommand::new()
+        .current_dir(in_folder.replace("\\", "/"))
+        .no_deps()
+        .exec()?;
+
+    let mut remaps = vec![(metadata.workspace_root.into_std_path_buf(), ".")];
+    let cargo_home = env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| directories::BaseDirs::new().map(|d| d.home_dir().join(".cargo")));
+    if let Some(cargo_home) = cargo_home {
+        remaps.push((cargo_home, "/cargo"));
+    }
+    remaps.push((sdk_path.to_path_buf(), "/msfs-sdk"));
+
+    Ok(remaps)
+}
+
+/// Gets the timestamp to build with: `SOURCE_DATE_EPOCH` if set, otherwise the time of the crate's last commit, otherwise 0
+///
+/// * `in_folder` - The path to the crate
+pub fn get_source_date_epoch(in_folder: &str) -> String {
+    if let Ok(epoch) = env::var("SOURCE_DATE_EPOCH") {
+        return epoch;
+    }
+
+    Command::new("git")
+        .args(["log", "-1", "--format=%ct"])
+        .current_dir(in_folder.replace("\\", "/"))
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|epoch| !epoch.is_empty())
+        .unwrap_or("0".to_string())
+}
@@ -0,0 +1,428 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use wasmi::{
+    core::{ValType, F32, F64},
+    Caller, Engine, Extern, ExternType, Func, FuncType, Instance, Linker, Memory, Module, Store,
+    Val,
+};
+
+use crate::print_info;
+
+// The module name WASI preview 1 functions are imported from
+const WASI_MODULE: &str = "wasi_snapshot_preview1";
+
+// WASI errno values, see https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#errno
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_BADF: i32 = 8;
+const ERRNO_FAULT: i32 = 21;
+const ERRNO_NOSYS: i32 = 52;
+
+// WASI file type of the standard streams
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+
+/// Configuration of the host functions given to a module
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeOptions {
+    /// The command line arguments seen by the module through WASI, including the program name
+    pub args: Vec<String>,
+    /// Values returned by stubbed imports, keyed by import name. Imports without a value return zero
+    pub stub_values: HashMap<String, f64>,
+    /// Whether to log the first call of every stubbed import
+    pub log_calls: bool,
+    /// Whether to collect stdout and stderr output in the host state instead of printing it
+    pub capture_output: bool,
+}
+
+/// State shared with host functions
+#[derive(Debug, Default)]
+pub struct HostState {
+    options: RuntimeOptions,
+    /// Number of calls per stubbed import, keyed by `module::name`
+    pub call_counts: BTreeMap<String, u64>,
+    /// Output written to stdout and stderr, if capturing output
+    pub output: Vec<u8>,
+    /// State of the pseudo random generator backing `random_get`
+    random_state: u64,
+}
+
+/// Gets the exported memory of the calling instance
+///
+/// * `caller` - The caller of a host function
+fn get_memory(caller: &Caller<'_, HostState>) -> Result<Memory, wasmi::Error> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new("module doesn't export its memory"))
+}
+
+/// Reads a little endian u32 from memory
+///
+/// * `memory` - The memory data
+/// * `offset` - The offset to read at
+fn read_u32(memory: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        memory
+            .get(offset..offset.checked_add(4)?)?
+            .try_into()
+            .ok()?,
+    ))
+}
+
+/// Writes bytes to memory, returning false if out of bounds
+///
+/// * `memory` - The memory data
+/// * `offset` - The offset to write at
+/// * `bytes` - The bytes to write
+fn write_bytes(memory: &mut [u8], offset: usize, bytes: &[u8]) -> bool {
+    let Some(end) = offset.checked_add(bytes.len()) else {
+        return false;
+    };
+    match memory.get_mut(offset..end) {
+        Some(slice) => {
+            slice.copy_from_slice(bytes);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Writes a list of strings the way `args_get` and `environ_get` expect: pointers into a buffer of nul terminated strings
+///
+/// * `memory` - The memory data
+/// * `pointers` - Where to write the pointers
+/// * `buffer` - Where to write the strings
+/// * `strings` - The strings
+fn write_string_list(memory: &mut [u8], pointers: usize, buffer: usize, strings: &[String]) -> i32 {
+    let mut offset = buffer;
+    for (i, string) in strings.iter().enumerate() {
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.push(0);
+        let Some(pointer) = i.checked_mul(4).and_then(|o| pointers.checked_add(o)) else {
+            return ERRNO_FAULT;
+        };
+        if !write_bytes(memory, pointer, &(offset as u32).to_le_bytes())
+            || !write_bytes(memory, offset, &bytes)
+        {
+            return ERRNO_FAULT;
+        }
+        offset += bytes.len();
+    }
+
+    ERRNO_SUCCESS
+}
+
+/// Implements a WASI preview 1 function, or returns None if it isn't supported
+///
+/// Only what's needed to print output and run std initialization is implemented. There are no files, so every other call fails
+///
+/// * `caller` - The caller of the function
+/// * `name` - The name of the WASI function
+/// * `params` - The parameters of the call
+fn call_wasi(
+    caller: &mut Caller<'_, HostState>,
+    name: &str,
+    params: &[Val],
+) -> Result<Option<i32>, wasmi::Error> {
+    // Pointers and lengths are unsigned, so they must not be sign extended
+    let param = |i: usize| params.get(i).and_then(Val::i32).unwrap_or_default() as u32 as usize;
+
+    match name {
+        "proc_exit" => Err(wasmi::Error::i32_exit(param(0) as i32)),
+        "fd_write" => {
+            let memory = get_memory(caller)?;
+            let data = memory.data(&*caller);
+            let (fd, iovs, iovs_len, nwritten) = (param(0), param(1), param(2), param(3));
+
+            let mut bytes = vec![];
+            for i in 0..iovs_len {
+                let Some(iov) = i.checked_mul(8).and_then(|o| iovs.checked_add(o)) else {
+                    return Ok(Some(ERRNO_FAULT));
+                };
+                let (Some(pointer), Some(length)) = (
+                    read_u32(data, iov),
+                    iov.checked_add(4).and_then(|o| read_u32(data, o)),
+                ) else {
+                    return Ok(Some(ERRNO_FAULT));
+                };
+                let (pointer, length) = (pointer as usize, length as usize);
+                let Some(slice) = pointer
+                    .checked_add(length)
+                    .and_then(|end| data.get(pointer..end))
+                else {
+                    return Ok(Some(ERRNO_FAULT));
+                };
+                bytes.extend_from_slice(slice);
+            }
+
+            let result = match fd {
+                1 | 2 if caller.data().options.capture_output => {
+                    caller.data_mut().output.extend_from_slice(&bytes);
+                    Ok(())
+                }
+                1 => io::stdout().write_all(&bytes),
+                2 => io::stderr().write_all(&bytes),
+                _ => return Ok(Some(ERRNO_BADF)),
+            };
+            if result.is_err() {
+                return Ok(Some(ERRNO_BADF));
+            }
+
+            let written = (bytes.len() as u32).to_le_bytes();
+            let data = memory.data_mut(&mut *caller);
+            Ok(Some(if write_bytes(data, nwritten, &written) {
+                ERRNO_SUCCESS
+            } else {
+                ERRNO_FAULT
+            }))
+        }
+        "fd_fdstat_get" => {
+            if param(0) > 2 {
+                return Ok(Some(ERRNO_BADF));
+            }
+            // fdstat is 24 bytes, with the file type as the first byte and everything else (flags, rights) left empty
+            let mut fdstat = [0u8; 24];
+            fdstat[0] = FILETYPE_CHARACTER_DEVICE;
+            let memory = get_memory(caller)?;
+            let data = memory.data_mut(&mut *caller);
+            Ok(Some(if write_bytes(data, param(1), &fdstat) {
+                ERRNO_SUCCESS
+            } else {
+                ERRNO_FAULT
+            }))
+        }
+        // No directories are preopened, which is signalled by returning EBADF for the first descriptor
+        "fd_prestat_get" => Ok(Some(ERRNO_BADF)),
+        "args_sizes_get" | "environ_sizes_get" => {
+            let strings = if name == "args_sizes_get" {
+                caller.data().options.args.clone()
+            } else {
+                vec![]
+            };
+            let buffer_size = strings.iter().map(|s| s.len() + 1).sum::<usize>() as u32;
+            let memory = get_memory(caller)?;
+            let data = memory.data_mut(&mut *caller);
+            Ok(Some(
+                if write_bytes(data, param(0), &(strings.len() as u32).to_le_bytes())
+                    && write_bytes(data, param(1), &buffer_size.to_le_bytes())
+                {
+                    ERRNO_SUCCESS
+                } else {
+                    ERRNO_FAULT
+                },
+            ))
+        }
+        "args_get" | "environ_get" => {
+            let strings = if name == "args_get" {
+                caller.data().options.args.clone()
+            } else {
+                vec![]
+            };
+            let memory = get_memory(caller)?;
+            let data = memory.data_mut(&mut *caller);
+            Ok(Some(write_string_list(data, param(0), param(1), &strings)))
+        }
+        "clock_time_get" => {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default();
+            let memory = get_memory(caller)?;
+            let data = memory.data_mut(&mut *caller);
+            Ok(Some(if write_bytes(data, param(2), &nanos.to_le_bytes()) {
+                ERRNO_SUCCESS
+            } else {
+                ERRNO_FAULT
+            }))
+        }
+        "random_get" => {
+            // The length comes from the guest, so make sure the buffer fits in memory before allocating it
+            let (buffer, length) = (param(0), param(1));
+            let memory = get_memory(caller)?;
+            if buffer
+                .checked_add(length)
+                .is_none_or(|end| end > memory.data(&*caller).len())
+            {
+                return Ok(Some(ERRNO_FAULT));
+            }
+
+            // Deterministic xorshift, so runs are reproducible
+            let mut bytes = vec![0u8; length];
+            let state = &mut caller.data_mut().random_state;
+            for byte in bytes.iter_mut() {
+                *state ^= *state << 13;
+                *state ^= *state >> 7;
+                *state ^= *state << 17;
+                *byte = *state as u8;
+            }
+            let data = memory.data_mut(&mut *caller);
+            Ok(Some(if write_bytes(data, buffer, &bytes) {
+                ERRNO_SUCCESS
+            } else {
+                ERRNO_FAULT
+            }))
+        }
+        "sched_yield" => Ok(Some(ERRNO_SUCCESS)),
+        _ => Ok(None),
+    }
+}
+
+/// Formats a value for logging
+///
+/// * `value` - The value to format
+fn format_val(value: &Val) -> String {
+    match value {
+        Val::I32(v) => v.to_string(),
+        Val::I64(v) => v.to_string(),
+        Val::F32(v) => v.to_float().to_string(),
+        Val::F64(v) => v.to_float().to_string(),
+        _ => format!("{value:?}"),
+    }
+}
+
+/// Converts a configured stub value to the result type of an import
+///
+/// * `value` - The configured value
+/// * `ty` - The result type
+fn stub_value(value: f64, ty: ValType) -> Val {
+    match ty {
+        ValType::I32 => Val::I32(value as i32),
+        ValType::I64 => Val::I64(value as i64),
+        ValType::F32 => Val::F32(F32::from_float(value as f32)),
+        ValType::F64 => Val::F64(F64::from_float(value)),
+        _ => Val::default(ty),
+    }
+}
+
+/// Defines a host function for a function import. WASI functions are implemented, everything else (MSFS, SimConnect, ...) is stubbed
+///
+/// * `linker` - The linker to define the function in
+/// * `module` - The module of the import
+/// * `name` - The name of the import
+/// * `ty` - The type of the import
+fn define_import(
+    linker: &mut Linker<HostState>,
+    module: &str,
+    name: &str,
+    ty: FuncType,
+) -> Result<()> {
+    let (module_name, import_name) = (module.to_string(), name.to_string());
+    let results = ty.results().to_vec();
+
+    linker.func_new(module, name, ty, move |mut caller, params, outputs| {
+        if module_name == WASI_MODULE {
+            let errno = match call_wasi(&mut caller, &import_name, params)? {
+                Some(errno) => errno,
+                None => ERRNO_NOSYS,
+            };
+            if let Some(output) = outputs.first_mut() {
+                *output = Val::I32(errno);
+            }
+            return Ok(());
+        }
+
+        let key = format!("{module_name}::{import_name}");
+        let state = caller.data_mut();
+        let count = state.call_counts.entry(key.clone()).or_default();
+        *count += 1;
+        if *count == 1 && state.options.log_calls {
+            let params = params.iter().map(format_val).collect::<Vec<_>>();
+            print_info(&format!("Stub called: {key}({})", params.join(", ")));
+        }
+
+        let value = state
+            .options
+            .stub_values
+            .get(&import_name)
+            .copied()
+            .unwrap_or_default();
+        for (output, ty) in outputs.iter_mut().zip(&results) {
+            *output = stub_value(value, *ty);
+        }
+
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+/// Instantiates a module with WASI and stubbed host functions for all of its imports
+///
+/// * `wasm` - The module bytes
+/// * `options` - Configuration of the host functions
+pub fn instantiate(wasm: &[u8], options: RuntimeOptions) -> Result<(Store<HostState>, Instance)> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).context("couldn't load module")?;
+    let mut store = Store::new(
+        &engine,
+        HostState {
+            options,
+            call_counts: BTreeMap::new(),
+            output: vec![],
+            random_state: 0x2545_f491_4f6c_dd1d,
+        },
+    );
+
+    let mut linker = Linker::new(&engine);
+    for import in module.imports() {
+        match import.ty() {
+            ExternType::Func(ty) => {
+                define_import(&mut linker, import.module(), import.name(), ty.clone())?
+            }
+            ExternType::Memory(ty) => {
+                let memory = Memory::new(&mut store, *ty).map_err(|e| anyhow!("{e}"))?;
+                linker.define(import.module(), import.name(), memory)?;
+            }
+            _ => {
+                return Err(anyhow!(
+                    "unsupported import {}::{}, only functions and memories can be provided",
+                    import.module(),
+                    import.name()
+                ))
+            }
+        }
+    }
+
+    let instance = linker.instantiate(&mut store, &module)?.start(&mut store)?;
+
+    Ok((store, instance))
+}
+
+/// Calls an exported function with the given parameters, returning its results
+///
+/// * `store` - The store of the instance
+/// * `instance` - The instance
+/// * `name` - The name of the export
+/// * `params` - The parameters
+pub fn call_export(
+    store: &mut Store<HostState>,
+    instance: &Instance,
+    name: &str,
+    params: &[Val],
+) -> Result<Vec<Val>> {
+    let func = get_export_func(store, instance, name)?;
+    let ty = func.ty(&*store);
+    let mut results = ty
+        .results()
+        .iter()
+        .map(|t| Val::default(*t))
+        .collect::<Vec<_>>();
+    func.call(&mut *store, params, &mut results)?;
+
+    Ok(results)
+}
+
+/// Gets an exported function
+///
+/// * `store` - The store of the instance
+/// * `instance` - The instance
+/// * `name` - The name of the export
+pub fn get_export_func(store: &Store<HostState>, instance: &Instance, name: &str) -> Result<Func> {
+    instance
+        .get_func(store, name)
+        .with_context(|| format!("module doesn't export function `{name}`"))
+}
@@ -1,17 +1,26 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs::{self, File},
     io::{self, Cursor, Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::Context;
 use cab::Cabinet;
+use directories::ProjectDirs;
 use msi::{Expr, Package, Row, Select};
-use serde::Deserialize;
+use rayon::ThreadPoolBuilder;
+use reqwest::blocking::Client;
+use semver::{BuildMetadata, Version};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
 use zip::ZipArchive;
 
-use crate::{get_data_dir, SimulatorVersion};
+use crate::SimulatorVersion;
 
 // Root URLs for SDK files for each version of MSFS
 const MSFS2020_SDK_URL: &str = "https://sdk.flightsimulator.com/files/";
@@ -20,8 +29,8 @@ const MSFS2024_SDK_URL: &str = "https://sdk.flightsimulator.com/msfs2024/files/"
 // File name of the manifest file located under the root URL
 const MANIFEST_FILE: &str = "sdk.json";
 
-// The download option key for the core SDK installer
-const CORE_INSTALLER_KEY: &str = "SDK Installer (Core)";
+/// The download option key for the core SDK installer
+pub const CORE_INSTALLER_KEY: &str = "SDK Installer (Core)";
 
 // The folder in the SDK MSI to extract from
 #[cfg(target_os = "windows")]
@@ -38,8 +47,22 @@ const MSFS2024_SDK_EXTRACT_FROM: &str = "./MSFS 2024 SDK/";
 const MSFS2020_FOLDER_NAME: &str = "msfs2020";
 const MSFS2024_FOLDER_NAME: &str = "msfs2024";
 
+// Folder within the data directory where the manifests are cached, one file per simulator
+const MANIFEST_CACHE_FOLDER_NAME: &str = "manifests";
+
 // File name within the local destination folder where the SDK version is stored
 const VERSION_FILE_NAME: &str = "version.txt";
+// File name within the local destination folder where the SHA-256 of the installer is stored
+const HASH_FILE_NAME: &str = "installer.sha256";
+// Folders of the core SDK extracted by minimal installs: the WASI sysroot and the headers crates are built against
+const MINIMAL_PREFIXES: &[&str] = &["WASM", "SimConnect SDK/include"];
+
+// Extensions of the folders an SDK is installed into before it replaces the installed one, and the installed one is moved to meanwhile
+const STAGING_EXTENSION: &str = "partial";
+const OLD_EXTENSION: &str = "old";
+
+// File name within the local destination folder where the installed components are stored
+const INSTALL_FILE_NAME: &str = "install.json";
 
 // WASI sysroot location, relative to the SDK installation. Valid for both SDK editions
 #[cfg(target_os = "windows")]
@@ -49,26 +72,404 @@ const WASI_SYSROOT_PATH: &str = "./WASM/wasi-sysroot";
 
 // Configuration
 const CHUNK_SIZE: u64 = 1024;
+// The most threads cabinets are decompressed on. Every thread holds the cabinet it's decompressing in memory, so this bounds memory use
+const MAX_EXTRACTION_THREADS: usize = 8;
+
+/// A boxed error, used as the source of errors from the MSI, CAB and ZIP parsers
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// An error while managing SDKs
+#[derive(Debug, Error)]
+pub enum SdkError {
+    /// The SDK for the simulator isn't installed
+    #[error("SDK not installed")]
+    NotInstalled,
+    /// No data directory could be determined for the current user
+    #[error("could not get project dir")]
+    DataDir,
+    /// The manifest of available SDK versions couldn't be fetched
+    #[error("couldn't fetch the SDK manifest")]
+    ManifestFetch(#[source] reqwest::Error),
+    /// The manifest of available SDK versions couldn't be parsed or is missing information
+    #[error("invalid SDK manifest: {0}")]
+    InvalidManifest(String),
+    /// The requested SDK version isn't offered by the manifest
+    #[error("SDK version {0} is no longer available for download")]
+    ReleaseNotFound(String),
+    /// The SDK installer couldn't be downloaded
+    #[error("couldn't download the SDK installer")]
+    Download(#[source] reqwest::Error),
+    /// The SDK installer couldn't be read
+    #[error("couldn't parse the SDK installer")]
+    MsiParse(#[source] BoxError),
+    /// The SDK files couldn't be extracted from the installer
+    #[error("couldn't extract the SDK")]
+    Extraction(#[source] BoxError),
+    /// The installer doesn't contain cabinets listed in its Media table that hold SDK files
+    #[error("the SDK installer is missing cabinets: {}", .0.join(", "))]
+    MissingCabinets(Vec<String>),
+    /// The downloaded installer doesn't have the expected SHA-256
+    #[error("the downloaded SDK installer has SHA-256 {actual}, but {expected} was expected")]
+    InstallerMismatch { expected: String, actual: String },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Receives progress updates while an SDK is installed
+pub trait InstallProgress {
+    /// Called as the installer is downloaded
+    ///
+    /// * `downloaded` - How many bytes were downloaded so far
+    /// * `total` - The size of the installer in bytes
+    fn download(&mut self, downloaded: u64, total: u64);
+
+    /// Called as files are extracted from the installer
+    ///
+    /// * `extracted` - How many files were extracted so far
+    /// * `total` - The number of files to extract
+    fn extract(&mut self, extracted: u64, total: u64);
+}
+
+/// Ignores progress
+impl InstallProgress for () {
+    fn download(&mut self, _downloaded: u64, _total: u64) {}
+
+    fn extract(&mut self, _extracted: u64, _total: u64) {}
+}
+
+/// Parses an SDK version string as a semantic version. Missing minor and patch components are treated as zero, and a fourth revision component like installers have is kept as build metadata unless it's zero
+///
+/// * `version` - The version string, e.g. `0.24.3` or `0.24.3.0`
+pub fn parse_sdk_version(version: &str) -> Option<Version> {
+    let version = version.trim().trim_start_matches(['v', 'V']);
+    let components = version.split('.').collect::<Vec<_>>();
+    if components.len() <= 4 && components.iter().all(|c| c.parse::<u64>().is_ok()) {
+        let component = |i: usize| components.get(i).map_or(Some(0), |c| c.parse().ok());
+        let mut parsed = Version::new(component(0)?, component(1)?, component(2)?);
+        if let Some(revision) = components.get(3).filter(|_| component(3) != Some(0)) {
+            parsed.build = BuildMetadata::new(revision).ok()?;
+        }
+        return Some(parsed);
+    }
+
+    Version::parse(version).ok()
+}
+
+/// Checks whether two SDK version strings are the same version, e.g. `0.24` and `0.24.0`. Versions that can't be parsed are compared as written
+///
+/// * `a` - The first version
+/// * `b` - The second version
+pub fn is_same_sdk_version(a: &str, b: &str) -> bool {
+    match (parse_sdk_version(a), parse_sdk_version(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Gets what's wrong with a manifest from an error, without the prefix manifest errors are displayed with
+///
+/// * `error` - The error
+fn manifest_problem(error: SdkError) -> String {
+    match error {
+        SdkError::InvalidManifest(problem) => problem,
+        e => e.to_string(),
+    }
+}
 
 /// A downloads "menu option" containing an optional value
 ///
 /// For our case, the downloads menu key we are using will always have a Some() value
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DownloadsMenuOption {
     pub value: Option<String>,
 }
 // A specific SDK (game) version
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GameVersion {
     /// A hashmap of menu titles to URLs (among other things, but we only care about URLs here)
     pub downloads_menu: HashMap<String, DownloadsMenuOption>,
-    /// Vec of SDK versions. In 2020, the last entry is latest, while in 2024 it is the oldest
+    /// Vec of SDK versions. The order differs between simulators, so the highest version is the one the release installs
     pub release_notes: Vec<String>,
 }
+
+impl GameVersion {
+    /// Gets the release note of the SDK version the release installs, which is the highest version in its release notes, along with its parsed version
+    fn latest_release_note(&self) -> Result<(Version, &str), SdkError> {
+        self.release_notes
+            .iter()
+            .filter_map(|v| Some((parse_sdk_version(v)?, v.as_str())))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .ok_or(SdkError::InvalidManifest(format!(
+                "no valid SDK version in release notes {:?}",
+                self.release_notes
+            )))
+    }
+
+    /// Gets the SDK version the release installs, which is the highest version in its release notes
+    pub fn sdk_version(&self) -> Result<Version, SdkError> {
+        Ok(self.latest_release_note()?.0)
+    }
+
+    /// Checks that the release has the fields needed to install it
+    pub fn validate(&self) -> Result<(), SdkError> {
+        let sdk_version = self.sdk_version()?;
+        if self
+            .downloads_menu
+            .get(CORE_INSTALLER_KEY)
+            .and_then(|o| o.value.as_deref())
+            .is_none_or(str::is_empty)
+        {
+            return Err(SdkError::InvalidManifest(format!(
+                "SDK {sdk_version} has no \"{CORE_INSTALLER_KEY}\" download"
+            )));
+        }
+
+        Ok(())
+    }
+}
 /// The manifest of available SDK versions
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SdkManifest {
     pub game_versions: Vec<GameVersion>,
+    /// Why releases were left out when the manifest was parsed, one message per release
+    #[serde(skip)]
+    pub skipped: Vec<String>,
+}
+
+/// A manifest whose releases haven't been parsed yet, so one invalid release doesn't reject the others
+#[derive(Deserialize)]
+struct RawSdkManifest {
+    game_versions: Vec<serde_json::Value>,
+}
+
+impl SdkManifest {
+    /// Parses a manifest, skipping the releases that can't be installed. Fails if no release can be
+    ///
+    /// * `contents` - The contents of an `sdk.json` file
+    pub fn parse(contents: &str) -> Result<Self, SdkError> {
+        let raw = serde_json::from_str::<RawSdkManifest>(contents)
+            .map_err(|e| SdkError::InvalidManifest(e.to_string()))?;
+        if raw.game_versions.is_empty() {
+            return Err(SdkError::InvalidManifest(
+                "no game versions in manifest".to_string(),
+            ));
+        }
+
+        let mut game_versions = vec![];
+        let mut skipped = vec![];
+        for (index, value) in raw.game_versions.into_iter().enumerate() {
+            let release = serde_json::from_value::<GameVersion>(value)
+                .map_err(|e| SdkError::InvalidManifest(e.to_string()))
+                .and_then(|r| r.validate().map(|_| r));
+            match release {
+                Ok(release) => game_versions.push(release),
+                Err(e) => skipped.push(format!("game version {index}: {}", manifest_problem(e))),
+            }
+        }
+        if game_versions.is_empty() {
+            return Err(SdkError::InvalidManifest(format!(
+                "no valid game versions in manifest ({})",
+                skipped.join("; ")
+            )));
+        }
+
+        Ok(Self {
+            game_versions,
+            skipped,
+        })
+    }
+
+    /// Checks that the manifest has releases, and that every one of them has the fields needed to install it
+    pub fn validate(&self) -> Result<(), SdkError> {
+        if self.game_versions.is_empty() {
+            return Err(SdkError::InvalidManifest(
+                "no game versions in manifest".to_string(),
+            ));
+        }
+
+        for (index, release) in self.game_versions.iter().enumerate() {
+            release.validate().map_err(|e| {
+                SdkError::InvalidManifest(format!("game version {index}: {}", manifest_problem(e)))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Gets the latest release in the manifest, regardless of the order of releases
+    pub fn latest_release(&self) -> Result<&GameVersion, SdkError> {
+        let mut latest = None;
+        for release in &self.game_versions {
+            let sdk_version = release.sdk_version()?;
+            if latest.as_ref().is_none_or(|(v, _)| sdk_version > *v) {
+                latest = Some((sdk_version, release));
+            }
+        }
+
+        latest.map(|(_, r)| r).ok_or(SdkError::InvalidManifest(
+            "can't find game version for SDK".to_string(),
+        ))
+    }
+
+    /// Finds the release that installs a specific SDK version
+    ///
+    /// * `release_number` - The SDK version, e.g. `1.2.3`
+    pub fn find_release(&self, release_number: &str) -> Result<&GameVersion, SdkError> {
+        let wanted = parse_sdk_version(release_number)
+            .ok_or(SdkError::ReleaseNotFound(release_number.to_string()))?;
+        self.game_versions
+            .iter()
+            .find(|r| r.sdk_version().ok().as_ref() == Some(&wanted))
+            .ok_or(SdkError::ReleaseNotFound(release_number.to_string()))
+    }
+}
+
+/// An SDK release published in a manifest
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteSdk {
+    pub simulator: SimulatorVersion,
+    pub version: String,
+    /// The full URLs of the release's downloads, keyed by downloads menu title
+    pub downloads: BTreeMap<String, String>,
+    /// Whether this is the installed SDK
+    pub installed: bool,
+}
+
+/// A manifest saved the last time it was fetched
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CachedManifest {
+    /// When the manifest was fetched, in seconds since the Unix epoch
+    pub fetched_at: u64,
+    pub manifest: SdkManifest,
+}
+
+/// A download of a release installed alongside the core SDK
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SdkComponent {
+    /// The downloads menu title of the installer, e.g. `SDK Installer (Extras)`
+    pub download: String,
+    /// The folder to extract, relative to the SDK folder of the installer, e.g. `Samples/`. Empty to extract everything
+    pub prefix: String,
+    /// The SDK folder of the installer, relative to its root, e.g. `MSFS SDK Extras`. None if it's the same as the core SDK's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+}
+
+impl SdkComponent {
+    /// Creates a component
+    ///
+    /// * `download` - The downloads menu title of the installer
+    /// * `prefix` - The folder to extract, relative to the SDK folder of the installer. Empty to extract everything
+    pub fn new(download: impl Into<String>, prefix: impl Into<String>) -> Self {
+        Self {
+            download: download.into(),
+            prefix: prefix.into(),
+            root: None,
+        }
+    }
+
+    /// Sets the SDK folder of the installer, for installers that don't keep their files in the same folder as the core SDK
+    ///
+    /// * `root` - The SDK folder of the installer, relative to its root
+    pub fn root(mut self, root: impl Into<String>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+}
+
+/// What to install along with the core SDK
+#[derive(Debug, Clone, Default)]
+pub struct InstallOptions {
+    pub components: Vec<SdkComponent>,
+    /// Only extract the WASI sysroot and headers of the core SDK, which is all builds need
+    pub minimal: bool,
+    /// The SHA-256 the core installer must have. The installed SDK is left untouched if it differs
+    pub installer_sha256: Option<String>,
+}
+
+impl InstallOptions {
+    /// Sets whether to only extract the WASI sysroot and headers of the core SDK
+    ///
+    /// * `minimal` - Whether to only extract what builds need
+    pub fn minimal(mut self, minimal: bool) -> Self {
+        self.minimal = minimal;
+        self
+    }
+
+    /// Sets the SHA-256 the core installer must have, e.g. the one a crate is locked to
+    ///
+    /// * `installer_sha256` - The expected SHA-256, or None to accept any installer
+    pub fn installer_sha256(mut self, installer_sha256: Option<String>) -> Self {
+        self.installer_sha256 = installer_sha256;
+        self
+    }
+
+    /// Adds a component to install alongside the core SDK
+    ///
+    /// * `component` - The component
+    pub fn component(mut self, component: SdkComponent) -> Self {
+        self.components.push(component);
+        self
+    }
+}
+
+/// A component installed alongside the core SDK
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledComponent {
+    #[serde(flatten)]
+    pub component: SdkComponent,
+    /// The SHA-256 of the installer the component was installed from
+    pub installer_sha256: String,
+}
+
+/// What was installed along with the core SDK, stored next to it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct InstallMetadata {
+    components: Vec<InstalledComponent>,
+    #[serde(default)]
+    minimal: bool,
+}
+
+impl InstallMetadata {
+    /// Writes the metadata into an SDK folder
+    ///
+    /// * `sdk_path` - The SDK folder
+    fn write(&self, sdk_path: &Path) -> Result<(), SdkError> {
+        fs::write(
+            sdk_path.join(INSTALL_FILE_NAME),
+            serde_json::to_string_pretty(self).map_err(io::Error::from)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// What to extract from one installer of a release
+#[derive(Debug, Clone, Copy)]
+struct Extraction<'a> {
+    /// The downloads menu title of the installer
+    download: &'a str,
+    /// The SDK folder of the installer, relative to its root. None if it's the same as the core SDK's
+    root: Option<&'a str>,
+    /// Only extract files under these folders, relative to the SDK folder of the installer
+    prefixes: &'a [&'a str],
+    /// Don't extract files under these folders, relative to the SDK folder of the installer
+    skipped: &'a [&'a str],
+    /// The SHA-256 the installer must have
+    expected_sha256: Option<&'a str>,
+}
+
+/// An installed SDK
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstalledSdk {
+    pub simulator: SimulatorVersion,
+    pub version: String,
+    /// The SHA-256 of the installer the SDK was installed from, if known
+    pub installer_sha256: Option<String>,
+    /// The components installed alongside the core SDK
+    pub components: Vec<InstalledComponent>,
+    /// Whether only the WASI sysroot and headers of the core SDK are installed
+    pub minimal: bool,
+    pub path: PathBuf,
 }
 
 /// Extracts the long file name from a string containing both short and long. This works for strings that are only the long file name as well
@@ -76,7 +477,7 @@ pub struct SdkManifest {
 /// See https://learn.microsoft.com/en-us/windows/win32/msi/filename
 ///
 /// * `string` - The string to parse from. Example value: `vacirzcc.h|WASM_Static_Library.h`
-fn get_long_file_name(string: &str) -> Result<&str> {
+fn get_long_file_name(string: &str) -> anyhow::Result<&str> {
     string
         .split("|")
         .last()
@@ -91,7 +492,10 @@ fn get_long_file_name(string: &str) -> Result<&str> {
 ///
 /// * `directory` - The directory key (DirectoryDir)
 /// * `directories` - A vec of rows from the directory table
-fn get_directory_parent<'a>(directory: &'a str, directories: &'a Vec<Row>) -> Result<PathBuf> {
+fn get_directory_parent<'a>(
+    directory: &'a str,
+    directories: &'a Vec<Row>,
+) -> anyhow::Result<PathBuf> {
     let row = directories
         .iter()
         .find(|d| d["Directory"].as_str() == Some(directory))
@@ -110,229 +514,152 @@ fn get_directory_parent<'a>(directory: &'a str, directories: &'a Vec<Row>) -> Re
     }
 }
 
-/// Gets the latest SDK version information for the given simulator
+/// Gets the root URL of the SDK files for the given simulator
 ///
 /// * `version` - The simulator version to get for
-pub fn get_latest_sdk_release(version: SimulatorVersion) -> Result<GameVersion> {
-    let response = reqwest::blocking::get(format!(
-        "{}{}",
-        if version == SimulatorVersion::Msfs2020 {
-            MSFS2020_SDK_URL
-        } else {
-            MSFS2024_SDK_URL
-        },
-        MANIFEST_FILE
-    ))?
-    .text()?;
-
-    let manifest = serde_json::from_str::<SdkManifest>(&response)?;
-
-    let latest_sdk = manifest
-        .game_versions
-        .first()
-        .context("can't find game version for SDK")?;
-
-    Ok(latest_sdk.clone())
-}
-
-/// Gets the latest SDK version string for the given simulator
-///
-/// Note: This differs from get_latest_sdk_release, as that returns a struct with extra data
-///
-/// * `version` - The simulator version to get for
-pub fn get_latest_sdk_version(version: SimulatorVersion) -> Result<String> {
-    let release_notes = get_latest_sdk_release(version)?.release_notes;
-
-    // 2020's release notes are ordered from oldest to most recent, while 2024 is most recent to oldest
-    let latest = if version == SimulatorVersion::Msfs2020 {
-        release_notes.last()
+fn get_sdk_root_url(version: SimulatorVersion) -> &'static str {
+    if version == SimulatorVersion::Msfs2020 {
+        MSFS2020_SDK_URL
     } else {
-        release_notes.first()
+        MSFS2024_SDK_URL
     }
-    .context("no available sdk version")?
-    .to_string();
-
-    Ok(latest)
 }
 
-/// Gets the desired path for the given simulator
+/// Gets the full URL of a download listed in a manifest
 ///
-/// * `version` The simulator version to get the path for
-pub fn get_sdk_path(version: SimulatorVersion) -> Result<PathBuf> {
-    Ok(
-        get_data_dir()?.join(if version == SimulatorVersion::Msfs2020 {
-            MSFS2020_FOLDER_NAME
-        } else {
-            MSFS2024_FOLDER_NAME
-        }),
-    )
+/// * `version` - The simulator version the manifest is for
+/// * `download_path` - The value of the downloads menu option, relative to the root URL
+pub fn get_download_url(version: SimulatorVersion, download_path: &str) -> String {
+    format!("{}{}", get_sdk_root_url(version), download_path)
 }
 
-/// Gets the WASI sysroot path for the given simulator
+/// Gets the SDK version string a release installs, as written in the manifest
 ///
-/// * `version` The simulator version to get the path for
-pub fn get_wasi_sysroot_path(version: SimulatorVersion) -> Result<PathBuf> {
-    Ok(get_sdk_path(version)?.join(WASI_SYSROOT_PATH))
+/// * `release` - The release
+pub fn get_release_number(release: &GameVersion) -> Result<String, SdkError> {
+    Ok(release.latest_release_note()?.1.to_string())
 }
 
-/// Gets the installed SDK version for the given simulator
-///
-/// * `version` - The simulator version to get for
-pub fn get_installed_sdk_version(version: SimulatorVersion) -> Result<Option<String>> {
-    Ok(
-        match File::open(get_sdk_path(version)?.join(VERSION_FILE_NAME)) {
-            Ok(mut file) => {
-                let mut version = String::new();
-                file.read_to_string(&mut version)?;
-                Some(version.trim().to_owned())
-            }
-            Err(_) => None,
-        },
-    )
+/// An opened SDK installer
+struct Installer {
+    msi: Package<Cursor<Vec<u8>>>,
+    /// The zip the MSI came in, which holds its external cabinets
+    zip: Option<ZipArchive<Cursor<Vec<u8>>>>,
 }
 
-/// Removes the installed SDK for the given simulator
-///
-/// * `version` The simulator version to delete the SDK for
-pub fn remove_sdk_version(version: SimulatorVersion) -> Result<()> {
-    let path = get_sdk_path(version)?;
+impl Installer {
+    /// Gets the path in the zip of a cabinet that isn't embedded in the MSI, if the zip contains it
+    ///
+    /// * `cabinet` - The cabinet name
+    fn zipped_cabinet_path(&self, cabinet: &str) -> Option<String> {
+        // File names are case insensitive on Windows, where the Media table is authored
+        self.zip
+            .as_ref()?
+            .file_names()
+            .find(|f| {
+                Path::new(f)
+                    .file_name()
+                    .is_some_and(|n| n.eq_ignore_ascii_case(cabinet))
+            })
+            .map(str::to_string)
+    }
 
-    // Clear the out directory and recreate it
-    if path.exists() {
-        fs::remove_dir_all(&path)?;
+    /// Checks whether the installer contains a cabinet listed in the Media table
+    ///
+    /// * `cabinet` - The cabinet name. Cabinets embedded in the MSI are prefixed with #, while others are files next to the MSI
+    fn has_cabinet(&self, cabinet: &str) -> bool {
+        match cabinet.strip_prefix('#') {
+            Some(stream_name) => self.msi.has_stream(stream_name),
+            None => self.zipped_cabinet_path(cabinet).is_some(),
+        }
     }
 
-    Ok(())
-}
+    /// Reads a cabinet listed in the Media table, returning None if the installer doesn't contain it
+    ///
+    /// * `cabinet` - The cabinet name. Cabinets embedded in the MSI are prefixed with #, while others are files next to the MSI
+    fn read_cabinet(&mut self, cabinet: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        if !self.has_cabinet(cabinet) {
+            return Ok(None);
+        }
 
-/// Installs the latest SDK version for the given simulator
-///
-/// * `version` - The simulator version to download for
-/// * `download_progress_callback` - An optional callback to report download statistics. Useful for logging. Parameters: `downloaded: u64, total: u64`
-pub fn install_latest_sdk<F>(
-    version: SimulatorVersion,
-    mut download_progress_callback: Option<F>,
-) -> Result<()>
-where
-    F: FnMut(u64, u64),
-{
-    // Clear and recreate the SDK path
-    let out_directory = get_sdk_path(version)?;
-    remove_sdk_version(version)?;
-    fs::create_dir_all(&out_directory)?;
-
-    // Get latest SDK data
-    let latest_sdk = get_latest_sdk_release(version)?;
-    let download_url = latest_sdk
-        .downloads_menu
-        .get(CORE_INSTALLER_KEY)
-        .context("can't find core installer download option")?
-        .value
-        .as_ref()
-        .context("can't find core installer download url")?;
-    let release_number = if version == SimulatorVersion::Msfs2020 {
-        latest_sdk
-            .release_notes
-            .last()
-            .context("couldn't get latest release number")?
-    } else {
-        latest_sdk
-            .release_notes
-            .first()
-            .context("couldn't get latest release number")?
-    };
-
-    // Download the installer
-    let mut response = reqwest::blocking::get(format!(
-        "{}{}",
-        if version == SimulatorVersion::Msfs2020 {
-            MSFS2020_SDK_URL
-        } else {
-            MSFS2024_SDK_URL
-        },
-        download_url
-    ))?;
-    let content_length = response
-        .content_length()
-        .context("couldn't get content length of response")?;
-
-    let mut file = Cursor::new(Vec::new());
-    loop {
-        let mut buf = [0u8; CHUNK_SIZE as usize];
-        match response.read(&mut buf) {
-            Ok(0) => break, // End of file
-            Ok(data_size) => {
-                file.write_all(&buf[0..data_size])?;
-                if let Some(callback) = download_progress_callback.as_mut() {
-                    callback(file.position() + data_size as u64, content_length);
+        let mut buffer = Vec::new();
+        match cabinet.strip_prefix('#') {
+            Some(stream_name) => {
+                self.msi
+                    .read_stream(stream_name)?
+                    .read_to_end(&mut buffer)?;
+            }
+            None => {
+                let cabinet_path = self.zipped_cabinet_path(cabinet);
+                if let (Some(zip_archive), Some(cabinet_path)) = (&mut self.zip, cabinet_path) {
+                    zip_archive
+                        .by_name(&cabinet_path)?
+                        .read_to_end(&mut buffer)?;
                 }
             }
-            Err(e) => return Err(anyhow!(e)),
         }
+
+        Ok(Some(buffer))
     }
+}
 
-    // Parse the MSI. Some releases are zipped MSI files with external CAB files, so we need to handle that. Otherwise, everything is included in the MSI.
-    let mut msi = if download_url.ends_with(".zip") {
-        let mut zip_archive = ZipArchive::new(file)?;
+/// Opens a downloaded installer. Some releases are zipped MSI files with external CAB files, which are read from the zip as needed. Otherwise, everything is included in the MSI.
+///
+/// * `installer` - The downloaded installer
+/// * `is_zip` - Whether the installer is a zipped MSI
+fn open_msi(installer: Cursor<Vec<u8>>, is_zip: bool) -> anyhow::Result<Installer> {
+    if !is_zip {
+        return Ok(Installer {
+            msi: Package::open(installer)?,
+            zip: None,
+        });
+    }
 
-        // Find the MSI file in the zip listing
-        let msi_file_name = zip_archive
-            .file_names()
-            .find(|f| f.ends_with(".msi"))
-            .context("couldn't find msi in zip")?
-            .to_string();
+    let mut zip_archive = ZipArchive::new(installer)?;
 
-        // Read the MSI archive to a buffer and then create the package
-        let mut msi_buffer = Vec::new();
-        zip_archive
-            .by_name(&msi_file_name)?
-            .read_to_end(&mut msi_buffer)?;
+    // Find the MSI file in the zip listing
+    let msi_file_name = zip_archive
+        .file_names()
+        .find(|f| f.ends_with(".msi"))
+        .context("couldn't find msi in zip")?
+        .to_string();
 
-        let mut package = Package::open(Cursor::new(msi_buffer))?;
+    // Read the MSI archive to a buffer and then create the package
+    let mut msi_buffer = Vec::new();
+    zip_archive
+        .by_name(&msi_file_name)?
+        .read_to_end(&mut msi_buffer)?;
 
-        // Since the CAB files are external, we need to manually add them to the package. TODO: This is *really* ineff
-        for cab_file_path in zip_archive
-            .file_names()
-            .filter_map(|f| {
-                if f.ends_with(".cab") {
-                    Some(f.to_string())
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>()
-        {
-            // Get the file name from the path
-            let cab_file_name = PathBuf::from(&cab_file_path)
-                .file_name()
-                .context("couldn't get file name")?
-                .to_str()
-                .context("couldn't convert to str")?
-                .to_string();
-
-            // Read the cab archive to a buffer
-            let mut cab_buffer = Vec::new();
-            zip_archive
-                .by_name(&cab_file_path)?
-                .read_to_end(&mut cab_buffer)?;
-
-            // Write the stream
-            let mut stream = package.write_stream(&cab_file_name)?;
-            stream.write_all(&cab_buffer)?;
-        }
-
-        package
-    } else {
-        Package::open(file)?
-    };
+    Ok(Installer {
+        msi: Package::open(Cursor::new(msi_buffer))?,
+        zip: Some(zip_archive),
+    })
+}
+
+/// A file in an MSI package
+struct MsiFile {
+    /// The full output relative path (e.g. ./Foo/Bar/Baz.qux)
+    path: PathBuf,
+    /// The cabinet holding the file, as listed in the Media table, if it's in one
+    cabinet: Option<String>,
+}
 
+/// Creates a map of the file ID to the file (e.g. filFQCSYDXD6IK3UAB8101TGG3B0387F7ZD to ./Foo/Bar/Baz.qux)
+///
+/// * `msi` - The MSI package
+fn get_file_map(msi: &mut Package<Cursor<Vec<u8>>>) -> anyhow::Result<HashMap<String, MsiFile>> {
     // Query the MSI tables for info on file and folder names (see https://learn.microsoft.com/en-us/windows/win32/msi/database-tables for info on the values)
     let query = Select::table("File")
         .inner_join(
             Select::table("Component"),
             Expr::col("Component.Component").eq(Expr::col("File.Component_")),
         )
-        .columns(&["File.File", "File.FileName", "Component.Directory_"]);
+        .columns(&[
+            "File.File",
+            "File.FileName",
+            "File.Sequence",
+            "Component.Directory_",
+        ]);
     let files = msi.select_rows(query)?.collect::<Vec<_>>();
     let directories = msi
         .select_rows(Select::table("Directory").columns(&[
@@ -341,8 +668,8 @@ where
             "DefaultDir",
         ]))?
         .collect::<Vec<_>>();
+    let cabinets = get_cabinets(msi)?;
 
-    // Create a map of the file ID to the full output relative path (e.g. filFQCSYDXD6IK3UAB8101TGG3B0387F7ZD to ./Foo/Bar/Baz.qux)
     let mut file_map = HashMap::new();
     for file in files {
         let file_id = file["File.File"].as_str().context("couldn't get file id")?;
@@ -357,59 +684,863 @@ where
                 .context("couldn't get file name")?,
             &directories,
         )?;
-        file_map.insert(file_id.to_string(), directory.join(file_name));
+        // Files are stored in cabinets in sequence order, and each medium holds the files up to its last sequence number. The Media table can't be joined on a range, so match it here
+        let sequence = file["File.Sequence"]
+            .as_int()
+            .context("couldn't get file sequence")?;
+        let cabinet = cabinets
+            .iter()
+            .find(|(last_sequence, _)| sequence <= *last_sequence)
+            .and_then(|(_, cabinet)| cabinet.clone());
+        file_map.insert(
+            file_id.to_string(),
+            MsiFile {
+                path: directory.join(file_name),
+                cabinet,
+            },
+        );
     }
 
-    // Write version file
-    let mut version_file = File::create(out_directory.join(VERSION_FILE_NAME))?;
-    version_file.write_all(release_number.as_bytes())?;
+    Ok(file_map)
+}
 
-    // Write SDK files
-    let extract_from = if version == SimulatorVersion::Msfs2020 {
-        MSFS2020_SDK_EXTRACT_FROM
-    } else {
-        MSFS2024_SDK_EXTRACT_FROM
-    };
-    // A more efficient way would be to find the stream associated with a file, but that is not possible. Given that, we must loop over all streams
-    for stream_name in msi.streams().collect::<Vec<_>>() {
-        let stream = msi.read_stream(&stream_name)?;
-        let mut cabinet = match Cabinet::new(stream) {
-            Ok(cabinet) => cabinet,
-            Err(_) => continue, // Not a cabinet file
+/// Gets the media of the MSI with the last sequence number of the files they hold, sorted by it, and their cabinet if they have one
+///
+/// * `msi` - The MSI package
+fn get_cabinets(msi: &mut Package<Cursor<Vec<u8>>>) -> anyhow::Result<Vec<(i32, Option<String>)>> {
+    let mut cabinets = vec![];
+    for media in msi.select_rows(Select::table("Media").columns(&["LastSequence", "Cabinet"]))? {
+        let last_sequence = media["LastSequence"]
+            .as_int()
+            .context("couldn't get media last sequence")?;
+        // Media without a cabinet are uncompressed source files, which the SDK installers don't use
+        cabinets.push((last_sequence, media["Cabinet"].as_str().map(str::to_string)));
+    }
+    cabinets.sort();
+
+    Ok(cabinets)
+}
+
+/// A message from a thread extracting a cabinet
+enum ExtractionEvent {
+    /// A file was written
+    File,
+    /// The cabinet is done, successfully or not
+    Cabinet(anyhow::Result<()>),
+}
+
+/// Extracts files from a cabinet, reporting each one extracted
+///
+/// * `cabinet_name` - The name of the cabinet
+/// * `cabinet` - The cabinet file
+/// * `files` - The map of file IDs to extract from the cabinet to where to extract them
+/// * `extracted` - Receives a message for every extracted file
+fn extract_cabinet(
+    cabinet_name: &str,
+    cabinet: Vec<u8>,
+    files: &HashMap<String, PathBuf>,
+    extracted: &Sender<ExtractionEvent>,
+) -> anyhow::Result<()> {
+    let mut cabinet = Cabinet::new(Cursor::new(cabinet))
+        .with_context(|| format!("couldn't read cabinet {cabinet_name}"))?;
+    for (file_id, out_file_path) in files {
+        // Ensure directories exist
+        let parent = out_file_path.parent().context("could not get parent")?;
+        fs::create_dir_all(parent)?;
+        // Write. The file identifier is the name of the file in the cabinet
+        let mut data = cabinet.read_file(file_id).with_context(|| {
+            format!(
+                "cabinet {cabinet_name} doesn't contain {}",
+                out_file_path.display()
+            )
+        })?;
+        let mut file = File::create(out_file_path)?;
+        io::copy(&mut data, &mut file)?;
+        // The receiver only goes away once every cabinet is done
+        let _ = extracted.send(ExtractionEvent::File);
+    }
+
+    Ok(())
+}
+
+/// Extracts the files under a folder of the MSI into the output directory. Returns the number of files extracted
+///
+/// * `installer` - The opened installer
+/// * `file_map` - The map of file IDs to the files in the MSI
+/// * `extract_from` - The folder in the MSI to extract
+/// * `prefixes` - Only extract files under these folders, relative to the folder to extract
+/// * `skipped` - Don't extract files under these folders, relative to the folder to extract
+/// * `out_directory` - Where to extract to
+/// * `progress` - Receives extraction progress
+fn extract_msi(
+    installer: &mut Installer,
+    file_map: &HashMap<String, MsiFile>,
+    extract_from: &Path,
+    prefixes: &[&str],
+    skipped: &[&str],
+    out_directory: &Path,
+    progress: &mut dyn InstallProgress,
+) -> Result<usize, SdkError> {
+    // Only extract the SDK files we care about, to their path relative to the folder we are extracting, grouped by the cabinet holding them
+    let mut wanted = BTreeMap::<&str, HashMap<String, PathBuf>>::new();
+    for (file_id, file) in file_map {
+        let Ok(relative_path) = file.path.strip_prefix(extract_from) else {
+            continue;
         };
-        // Since there is a weird ownership model of the crate we use, we need to go ahead and extract all the file names
-        let files = cabinet
-            .folder_entries()
-            .flat_map(|f| f.file_entries())
-            .map(|f| f.name().to_string())
-            .collect::<Vec<_>>();
-        for cab_file_name in files {
-            // cab_file_name will be the file identifier, which we will query from the file path hashmap
-            let entry = file_map
-                .get(&cab_file_name)
-                .context("couldn't find mapped file name")?;
-
-            // Only extract the SDK files we care about
-            if entry
-                .as_os_str()
-                .to_str()
-                .context("couldn't convert to str")?
-                .starts_with(extract_from)
+        if !prefixes.iter().any(|p| relative_path.starts_with(p))
+            || skipped.iter().any(|p| relative_path.starts_with(p))
+        {
+            continue;
+        }
+        let cabinet = file.cabinet.as_deref().ok_or_else(|| {
+            SdkError::Extraction(
+                format!("{} isn't stored in a cabinet", file.path.display()).into(),
+            )
+        })?;
+        wanted
+            .entry(cabinet)
+            .or_default()
+            .insert(file_id.clone(), out_directory.join(relative_path));
+    }
+
+    // Only the cabinets holding files we want are needed, so fail up front if any of them are missing
+    let missing = wanted
+        .keys()
+        .filter(|c| !installer.has_cabinet(c))
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(SdkError::MissingCabinets(missing));
+    }
+
+    // Cabinets are independent, so decompress them in parallel and report files as they are written
+    let threads = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_EXTRACTION_THREADS);
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| SdkError::Extraction(e.into()))?;
+    let total = wanted.values().map(HashMap::len).sum::<usize>() as u64;
+    let (sender, receiver) = mpsc::channel();
+    pool.in_place_scope(|scope| {
+        let mut cabinets = wanted.iter();
+        let mut running = 0;
+        let mut extracted = 0;
+        let mut error = None;
+        loop {
+            // The installer can only be read from this thread, so a cabinet is only read once a thread is free to decompress it. This bounds how many are in memory at once
+            if running < threads && error.is_none() {
+                if let Some((cabinet_name, files)) = cabinets.next() {
+                    let cabinet = installer
+                        .read_cabinet(cabinet_name)
+                        .and_then(|c| c.with_context(|| format!("missing cabinet {cabinet_name}")));
+                    match cabinet {
+                        Ok(cabinet) => {
+                            let sender = sender.clone();
+                            scope.spawn(move |_| {
+                                let result = extract_cabinet(cabinet_name, cabinet, files, &sender);
+                                let _ = sender.send(ExtractionEvent::Cabinet(result));
+                            });
+                            running += 1;
+                        }
+                        Err(e) => error = Some(e),
+                    }
+                    continue;
+                }
+            }
+            if running == 0 {
+                break;
+            }
+
+            // This thread holds a sender, so the channel is never closed
+            match receiver.recv() {
+                Ok(ExtractionEvent::File) => {
+                    extracted += 1;
+                    progress.extract(extracted, total);
+                }
+                Ok(ExtractionEvent::Cabinet(result)) => {
+                    running -= 1;
+                    if let Err(e) = result {
+                        error.get_or_insert(e);
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+
+        match error {
+            Some(e) => Err(SdkError::Extraction(e.into())),
+            None => Ok(extracted as usize),
+        }
+    })
+}
+
+/// Manages the SDKs installed in a data directory
+#[derive(Debug, Clone)]
+pub struct SdkManager {
+    data_dir: PathBuf,
+    client: Client,
+    warn: fn(&str),
+}
+
+impl SdkManager {
+    /// Creates a manager for SDKs installed in the given data directory
+    ///
+    /// * `data_dir` - The directory SDKs are installed in, one folder per simulator
+    pub fn new(data_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            data_dir: data_dir.into(),
+            client: Client::new(),
+            warn: |_| {},
+        }
+    }
+
+    /// Creates a manager for SDKs installed in the user's data directory, the one used by the CLI
+    pub fn from_default_data_dir() -> Result<Self, SdkError> {
+        Ok(Self::new(
+            ProjectDirs::from("", "", "cargo-msfs")
+                .ok_or(SdkError::DataDir)?
+                .data_dir(),
+        ))
+    }
+
+    /// Sets the HTTP client used to fetch manifests and installers, e.g. to configure proxies or timeouts
+    ///
+    /// * `client` - The HTTP client
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Sets what receives warnings, e.g. about invalid releases skipped in a fetched manifest. They're dropped by default
+    ///
+    /// * `warn` - Receives every warning
+    pub fn with_warnings(mut self, warn: fn(&str)) -> Self {
+        self.warn = warn;
+        self
+    }
+
+    /// Gets the directory SDKs are installed in
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// Gets the desired path for the given simulator
+    ///
+    /// * `version` The simulator version to get the path for
+    pub fn sdk_path(&self, version: SimulatorVersion) -> PathBuf {
+        self.data_dir
+            .join(if version == SimulatorVersion::Msfs2020 {
+                MSFS2020_FOLDER_NAME
+            } else {
+                MSFS2024_FOLDER_NAME
+            })
+    }
+
+    /// Gets the WASI sysroot path for the given simulator
+    ///
+    /// * `version` The simulator version to get the path for
+    pub fn wasi_sysroot_path(&self, version: SimulatorVersion) -> PathBuf {
+        self.sdk_path(version).join(WASI_SYSROOT_PATH)
+    }
+
+    /// Gets the installed SDK version for the given simulator
+    ///
+    /// * `version` - The simulator version to get for
+    pub fn installed_version(&self, version: SimulatorVersion) -> Result<Option<String>, SdkError> {
+        match fs::read_to_string(self.sdk_path(version).join(VERSION_FILE_NAME)) {
+            Ok(version) => Ok(Some(version.trim().to_owned())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Gets the SHA-256 of the installer the SDK for the given simulator was installed from, if known
+    ///
+    /// * `version` - The simulator version to get for
+    pub fn installed_hash(&self, version: SimulatorVersion) -> Result<Option<String>, SdkError> {
+        match fs::read_to_string(self.sdk_path(version).join(HASH_FILE_NAME)) {
+            Ok(hash) => Ok(Some(hash.trim().to_owned())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Gets what was installed along with the core SDK for the given simulator. SDKs installed before it was tracked have nothing else installed
+    ///
+    /// * `version` - The simulator version to get for
+    fn install_metadata(&self, version: SimulatorVersion) -> Result<InstallMetadata, SdkError> {
+        match fs::read_to_string(self.sdk_path(version).join(INSTALL_FILE_NAME)) {
+            Ok(contents) => Ok(serde_json::from_str(&contents).map_err(io::Error::from)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(InstallMetadata::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Gets the options the SDK for the given simulator was installed with
+    ///
+    /// * `version` - The simulator version to get for
+    pub fn installed_options(&self, version: SimulatorVersion) -> Result<InstallOptions, SdkError> {
+        let metadata = self.install_metadata(version)?;
+        Ok(InstallOptions {
+            components: metadata
+                .components
+                .into_iter()
+                .map(|c| c.component)
+                .collect(),
+            minimal: metadata.minimal,
+            installer_sha256: None,
+        })
+    }
+
+    /// Gets the installed SDK for the given simulator
+    ///
+    /// * `version` - The simulator version to get for
+    pub fn installed(&self, version: SimulatorVersion) -> Result<Option<InstalledSdk>, SdkError> {
+        let Some(installed_version) = self.installed_version(version)? else {
+            return Ok(None);
+        };
+
+        let metadata = self.install_metadata(version)?;
+        Ok(Some(InstalledSdk {
+            simulator: version,
+            version: installed_version,
+            installer_sha256: self.installed_hash(version)?,
+            components: metadata.components,
+            minimal: metadata.minimal,
+            path: self.sdk_path(version),
+        }))
+    }
+
+    /// Lists the installed SDKs of every simulator
+    pub fn list(&self) -> Result<Vec<InstalledSdk>, SdkError> {
+        let mut installed = vec![];
+        for version in [SimulatorVersion::Msfs2020, SimulatorVersion::Msfs2024] {
+            installed.extend(self.installed(version)?);
+        }
+
+        Ok(installed)
+    }
+
+    /// Gets the paths needed to build against the installed SDK for the given simulator: the SDK root and the WASI sysroot
+    ///
+    /// * `version` - The simulator version to get for
+    pub fn resolve_paths(&self, version: SimulatorVersion) -> Result<(PathBuf, PathBuf), SdkError> {
+        if self.installed_version(version)?.is_none() {
+            return Err(SdkError::NotInstalled);
+        }
+
+        Ok((self.sdk_path(version), self.wasi_sysroot_path(version)))
+    }
+
+    /// Gets the path the manifest for the given simulator is cached at
+    ///
+    /// * `version` - The simulator version to get for
+    fn manifest_cache_path(&self, version: SimulatorVersion) -> PathBuf {
+        let file_name = if version == SimulatorVersion::Msfs2020 {
+            MSFS2020_FOLDER_NAME
+        } else {
+            MSFS2024_FOLDER_NAME
+        };
+
+        self.data_dir
+            .join(MANIFEST_CACHE_FOLDER_NAME)
+            .join(format!("{file_name}.json"))
+    }
+
+    /// Fetches the manifest of available SDK versions for the given simulator, caching it for when there is no network
+    ///
+    /// * `version` - The simulator version to get for
+    pub fn manifest(&self, version: SimulatorVersion) -> Result<SdkManifest, SdkError> {
+        let response = self
+            .client
+            .get(format!("{}{}", get_sdk_root_url(version), MANIFEST_FILE))
+            .send()
+            .and_then(|r| r.error_for_status())
+            .and_then(|r| r.text())
+            .map_err(SdkError::ManifestFetch)?;
+
+        let manifest = SdkManifest::parse(&response)?;
+        for problem in &manifest.skipped {
+            (self.warn)(&format!(
+                "ignoring an invalid release in the {version:?} SDK manifest: {problem}"
+            ));
+        }
+
+        // The cache is only a fallback, so failing to write it shouldn't fail the fetch
+        let cached = CachedManifest {
+            fetched_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            manifest,
+        };
+        let cache_path = self.manifest_cache_path(version);
+        if let (Some(parent), Ok(contents)) = (cache_path.parent(), serde_json::to_string(&cached))
+        {
+            let _ = fs::create_dir_all(parent).and_then(|_| fs::write(&cache_path, contents));
+        }
+
+        Ok(cached.manifest)
+    }
+
+    /// Gets the manifest cached the last time it was fetched for the given simulator, if there is one
+    ///
+    /// * `version` - The simulator version to get for
+    pub fn cached_manifest(
+        &self,
+        version: SimulatorVersion,
+    ) -> Result<Option<CachedManifest>, SdkError> {
+        match fs::read_to_string(self.manifest_cache_path(version)) {
+            // A corrupt cache is as good as none
+            Ok(contents) => Ok(serde_json::from_str::<CachedManifest>(&contents)
+                .ok()
+                .filter(|c| c.manifest.validate().is_ok())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Lists every SDK release published for the given simulator, latest first
+    ///
+    /// * `version` - The simulator version to list for
+    pub fn list_remote(&self, version: SimulatorVersion) -> Result<Vec<RemoteSdk>, SdkError> {
+        let installed_version = self.installed_version(version)?;
+
+        let mut releases = self
+            .manifest(version)?
+            .game_versions
+            .iter()
+            .map(|release| {
+                let release_number = get_release_number(release)?;
+                Ok(RemoteSdk {
+                    simulator: version,
+                    installed: installed_version
+                        .as_deref()
+                        .is_some_and(|v| is_same_sdk_version(v, &release_number)),
+                    version: release_number,
+                    downloads: release
+                        .downloads_menu
+                        .iter()
+                        .filter_map(|(title, option)| {
+                            Some((
+                                title.clone(),
+                                get_download_url(version, option.value.as_ref()?),
+                            ))
+                        })
+                        .collect(),
+                })
+            })
+            .collect::<Result<Vec<_>, SdkError>>()?;
+
+        // The manifest isn't ordered consistently between simulators
+        releases.sort_by_key(|r| std::cmp::Reverse(parse_sdk_version(&r.version)));
+        Ok(releases)
+    }
+
+    /// Gets the latest SDK version information for the given simulator
+    ///
+    /// * `version` - The simulator version to get for
+    pub fn latest_release(&self, version: SimulatorVersion) -> Result<GameVersion, SdkError> {
+        Ok(self.manifest(version)?.latest_release()?.clone())
+    }
+
+    /// Gets the latest SDK version string for the given simulator
+    ///
+    /// Note: This differs from latest_release, as that returns a struct with extra data
+    ///
+    /// * `version` - The simulator version to get for
+    pub fn latest_version(&self, version: SimulatorVersion) -> Result<String, SdkError> {
+        get_release_number(&self.latest_release(version)?)
+    }
+
+    /// Finds the release that installs a specific SDK version for the given simulator
+    ///
+    /// * `version` - The simulator version to get for
+    /// * `release_number` - The SDK version, e.g. `1.2.3`
+    pub fn find_release(
+        &self,
+        version: SimulatorVersion,
+        release_number: &str,
+    ) -> Result<GameVersion, SdkError> {
+        Ok(self
+            .manifest(version)?
+            .find_release(release_number)?
+            .clone())
+    }
+
+    /// Removes the installed SDK for the given simulator. Returns whether there was anything to remove
+    ///
+    /// * `version` The simulator version to delete the SDK for
+    pub fn remove(&self, version: SimulatorVersion) -> Result<bool, SdkError> {
+        let path = self.sdk_path(version);
+
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    /// Installs the latest SDK version for the given simulator, replacing the installed SDK
+    ///
+    /// * `version` - The simulator version to download for
+    /// * `options` - What to install along with the core SDK
+    /// * `progress` - Receives download progress
+    pub fn install_latest(
+        &self,
+        version: SimulatorVersion,
+        options: &InstallOptions,
+        progress: &mut dyn InstallProgress,
+    ) -> Result<(), SdkError> {
+        self.install_release(version, &self.latest_release(version)?, options, progress)
+    }
+
+    /// Updates the installed SDK for the given simulator to the latest version, along with its installed components. Returns whether an update was installed
+    ///
+    /// * `version` - The simulator version to update for
+    /// * `progress` - Receives download progress
+    pub fn update(
+        &self,
+        version: SimulatorVersion,
+        progress: &mut dyn InstallProgress,
+    ) -> Result<bool, SdkError> {
+        let Some(installed_version) = self.installed_version(version)? else {
+            return Err(SdkError::NotInstalled);
+        };
+
+        let latest_release = self.latest_release(version)?;
+        if is_same_sdk_version(&get_release_number(&latest_release)?, &installed_version) {
+            return Ok(false);
+        }
+
+        self.install_release(
+            version,
+            &latest_release,
+            &self.installed_options(version)?,
+            progress,
+        )?;
+        Ok(true)
+    }
+
+    /// Installs the rest of the core SDK for the given simulator if only what builds need was installed, keeping the installed version and components. Only the core installer is downloaded again, and only the files a minimal install skipped are extracted into the installed SDK. Returns whether anything was installed
+    ///
+    /// * `version` - The simulator version to complete the SDK for
+    /// * `progress` - Receives download progress
+    pub fn complete(
+        &self,
+        version: SimulatorVersion,
+        progress: &mut dyn InstallProgress,
+    ) -> Result<bool, SdkError> {
+        let Some(installed_version) = self.installed_version(version)? else {
+            return Err(SdkError::NotInstalled);
+        };
+
+        let mut metadata = self.install_metadata(version)?;
+        if !metadata.minimal {
+            return Ok(false);
+        }
+
+        // The rest must come from the same installer, or the SDK would mix files of two installers
+        let release = self.find_release(version, &installed_version)?;
+        let installed_hash = self.installed_hash(version)?;
+        let rest = Extraction {
+            download: CORE_INSTALLER_KEY,
+            root: None,
+            prefixes: &[""],
+            skipped: MINIMAL_PREFIXES,
+            expected_sha256: installed_hash.as_deref(),
+        };
+        let sdk_path = self.sdk_path(version);
+        self.install_component(version, &release, &rest, &sdk_path, progress)?;
+
+        metadata.minimal = false;
+        metadata.write(&sdk_path)?;
+        Ok(true)
+    }
+
+    /// Installs components alongside the installed SDK for the given simulator, from the installed release. Components that are already installed are skipped. Returns the components that were installed
+    ///
+    /// * `version` - The simulator version to install the components for
+    /// * `components` - The components to install
+    /// * `progress` - Receives download progress
+    pub fn add_components(
+        &self,
+        version: SimulatorVersion,
+        components: &[SdkComponent],
+        progress: &mut dyn InstallProgress,
+    ) -> Result<Vec<SdkComponent>, SdkError> {
+        let Some(installed_version) = self.installed_version(version)? else {
+            return Err(SdkError::NotInstalled);
+        };
+
+        let sdk_path = self.sdk_path(version);
+        let mut metadata = self.install_metadata(version)?;
+        let mut added = vec![];
+        let mut release = None;
+        for component in components {
+            if metadata
+                .components
+                .iter()
+                .any(|c| &c.component == component)
             {
-                // Calculate the path relative to the folder we are extracting
-                let out_file_path = out_directory.join(entry.strip_prefix(extract_from)?);
-                // Ensure directories exist
-                let parent = out_file_path.parent().context("could not get parent")?;
-                if !parent.exists() {
-                    fs::create_dir_all(parent)?;
+                continue;
+            }
+
+            let release = match &release {
+                Some(release) => release,
+                None => release.insert(self.find_release(version, &installed_version)?),
+            };
+            let installed =
+                self.install_extra_component(version, release, component, &sdk_path, progress)?;
+            metadata.components.push(installed);
+            // Record every component once it's extracted, so a later failure doesn't lose track of it
+            metadata.write(&sdk_path)?;
+            added.push(component.clone());
+        }
+
+        Ok(added)
+    }
+
+    /// Downloads an SDK installer
+    ///
+    /// * `version` - The simulator version the installer is for
+    /// * `download_url` - The URL of the installer, relative to the root URL of the simulator's SDK files
+    /// * `progress` - Receives download progress
+    fn download_installer(
+        &self,
+        version: SimulatorVersion,
+        download_url: &str,
+        progress: &mut dyn InstallProgress,
+    ) -> Result<Cursor<Vec<u8>>, SdkError> {
+        let mut response = self
+            .client
+            .get(get_download_url(version, download_url))
+            .send()
+            .and_then(|r| r.error_for_status())
+            .map_err(SdkError::Download)?;
+        // Not every server reports the length, in which case the total is unknown (0)
+        let content_length = response.content_length().unwrap_or(0);
+
+        let mut file = Cursor::new(Vec::new());
+        loop {
+            let mut buf = [0u8; CHUNK_SIZE as usize];
+            match response.read(&mut buf) {
+                Ok(0) => break, // End of file
+                Ok(data_size) => {
+                    file.write_all(&buf[0..data_size])?;
+                    progress.download(file.position(), content_length);
                 }
-                // Write
-                let mut file = File::create(out_file_path)?;
-                let mut data = cabinet.read_file(&cab_file_name)?;
-                io::copy(&mut data, &mut file)?;
+                Err(e) => return Err(e.into()),
             }
         }
+        file.set_position(0);
+
+        Ok(file)
     }
 
-    Ok(())
+    /// Downloads an installer of a release and extracts its SDK files. Returns the SHA-256 of the installer
+    ///
+    /// * `version` - The simulator version the release is for
+    /// * `release` - The release
+    /// * `extraction` - The installer to download and what to extract from it
+    /// * `out_directory` - Where to extract to
+    /// * `progress` - Receives download progress
+    fn install_component(
+        &self,
+        version: SimulatorVersion,
+        release: &GameVersion,
+        extraction: &Extraction,
+        out_directory: &Path,
+        progress: &mut dyn InstallProgress,
+    ) -> Result<String, SdkError> {
+        let Extraction {
+            download,
+            root,
+            prefixes,
+            skipped,
+            expected_sha256,
+        } = *extraction;
+        let download_url = release
+            .downloads_menu
+            .get(download)
+            .and_then(|o| o.value.as_ref())
+            .ok_or(SdkError::InvalidManifest(format!(
+                "can't find \"{download}\" download url"
+            )))?;
+
+        let file = self.download_installer(version, download_url, progress)?;
+
+        // Record the installer hash so lockfiles can tell apart re-published releases with the same version
+        let installer_hash = format!("{:x}", Sha256::digest(file.get_ref()));
+        if let Some(expected) = expected_sha256 {
+            if expected != installer_hash {
+                return Err(SdkError::InstallerMismatch {
+                    expected: expected.to_string(),
+                    actual: installer_hash,
+                });
+            }
+        }
+
+        let mut installer = open_msi(file, download_url.ends_with(".zip"))
+            .map_err(|e| SdkError::MsiParse(e.into()))?;
+        let file_map =
+            get_file_map(&mut installer.msi).map_err(|e| SdkError::MsiParse(e.into()))?;
+
+        // Write SDK files
+        let extract_from = match root {
+            Some(root) => Path::new(".").join(root),
+            None if version == SimulatorVersion::Msfs2020 => {
+                PathBuf::from(MSFS2020_SDK_EXTRACT_FROM)
+            }
+            None => PathBuf::from(MSFS2024_SDK_EXTRACT_FROM),
+        };
+        let extracted = extract_msi(
+            &mut installer,
+            &file_map,
+            &extract_from,
+            prefixes,
+            skipped,
+            out_directory,
+            progress,
+        )?;
+        if extracted == 0 {
+            return Err(SdkError::Extraction(
+                format!(
+                    "\"{download}\" has no files under \"{}\" matching {prefixes:?}",
+                    extract_from.display()
+                )
+                .into(),
+            ));
+        }
+
+        Ok(installer_hash)
+    }
+
+    /// Downloads the installer of a component and extracts it alongside the core SDK
+    ///
+    /// * `version` - The simulator version the release is for
+    /// * `release` - The release
+    /// * `component` - The component
+    /// * `out_directory` - The folder of the core SDK
+    /// * `progress` - Receives download progress
+    fn install_extra_component(
+        &self,
+        version: SimulatorVersion,
+        release: &GameVersion,
+        component: &SdkComponent,
+        out_directory: &Path,
+        progress: &mut dyn InstallProgress,
+    ) -> Result<InstalledComponent, SdkError> {
+        let extraction = Extraction {
+            download: &component.download,
+            root: component.root.as_deref(),
+            prefixes: &[&component.prefix],
+            skipped: &[],
+            expected_sha256: None,
+        };
+        let installer_sha256 =
+            self.install_component(version, release, &extraction, out_directory, progress)?;
+
+        Ok(InstalledComponent {
+            component: component.clone(),
+            installer_sha256,
+        })
+    }
+
+    /// Installs a specific SDK release for the given simulator, replacing the installed SDK once the new one is complete. The installed SDK is left untouched if the install fails
+    ///
+    /// * `version` - The simulator version to download for
+    /// * `release` - The release to install
+    /// * `options` - What to install along with the core SDK
+    /// * `progress` - Receives download progress
+    pub fn install_release(
+        &self,
+        version: SimulatorVersion,
+        release: &GameVersion,
+        options: &InstallOptions,
+        progress: &mut dyn InstallProgress,
+    ) -> Result<(), SdkError> {
+        // Install next to the installed SDK, and only replace it once everything was extracted
+        let sdk_path = self.sdk_path(version);
+        let out_directory = sdk_path.with_extension(STAGING_EXTENSION);
+        if out_directory.exists() {
+            fs::remove_dir_all(&out_directory)?;
+        }
+        fs::create_dir_all(&out_directory)?;
+        let result = self.install_release_into(version, release, options, &out_directory, progress);
+        if result.is_err() {
+            let _ = fs::remove_dir_all(&out_directory);
+            return result;
+        }
+
+        // Move the old SDK aside first, so the SDK path is only ever missing for an instant
+        let old_path = sdk_path.with_extension(OLD_EXTENSION);
+        if old_path.exists() {
+            fs::remove_dir_all(&old_path)?;
+        }
+        if sdk_path.exists() {
+            fs::rename(&sdk_path, &old_path)?;
+        }
+        fs::rename(&out_directory, &sdk_path)?;
+        if old_path.exists() {
+            fs::remove_dir_all(&old_path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Installs a specific SDK release into a folder
+    ///
+    /// * `version` - The simulator version to download for
+    /// * `release` - The release to install
+    /// * `options` - What to install along with the core SDK
+    /// * `out_directory` - Where to install to
+    /// * `progress` - Receives download progress
+    fn install_release_into(
+        &self,
+        version: SimulatorVersion,
+        release: &GameVersion,
+        options: &InstallOptions,
+        out_directory: &Path,
+        progress: &mut dyn InstallProgress,
+    ) -> Result<(), SdkError> {
+        let release_number = get_release_number(release)?;
+
+        let core = Extraction {
+            download: CORE_INSTALLER_KEY,
+            root: None,
+            prefixes: if options.minimal {
+                MINIMAL_PREFIXES
+            } else {
+                &[""]
+            },
+            skipped: &[],
+            expected_sha256: options.installer_sha256.as_deref(),
+        };
+        let installer_hash =
+            self.install_component(version, release, &core, out_directory, progress)?;
+
+        let mut metadata = InstallMetadata {
+            components: vec![],
+            minimal: options.minimal,
+        };
+        for component in &options.components {
+            metadata.components.push(self.install_extra_component(
+                version,
+                release,
+                component,
+                out_directory,
+                progress,
+            )?);
+        }
+
+        // Write the version file last, so an interrupted install isn't mistaken for a complete one
+        metadata.write(out_directory)?;
+        fs::write(out_directory.join(HASH_FILE_NAME), installer_hash)?;
+        fs::write(out_directory.join(VERSION_FILE_NAME), release_number)?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,257 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{anyhow, Result};
+use wasmi::{
+    core::{ValType, F64},
+    Instance, Store, Val,
+};
+
+use crate::{
+    print_error, print_info, print_success,
+    runtime::{call_export, get_export_func, instantiate, HostState, RuntimeOptions},
+};
+
+// Gauge service IDs, see gauges.h in the SDK
+const PANEL_SERVICE_PRE_INSTALL: i32 = 2;
+const PANEL_SERVICE_POST_INSTALL: i32 = 3;
+const PANEL_SERVICE_PRE_DRAW: i32 = 10;
+const PANEL_SERVICE_PRE_KILL: i32 = 12;
+
+// Suffix of exported gauge callbacks
+const GAUGE_CALLBACK_SUFFIX: &str = "_gauge_callback";
+
+// Size of the sGaugeDrawData struct passed to PANEL_SERVICE_PRE_DRAW
+const DRAW_DATA_SIZE: i32 = 48;
+// Simulated frame rate, used for the draw data time delta
+const FRAME_RATE: f64 = 60.0;
+// Simulated window size, used for the draw data
+const WINDOW_SIZE: (i32, i32) = (1024, 768);
+
+/// Calls an export, printing and counting a failure if it traps
+///
+/// * `store` - The store of the instance
+/// * `instance` - The instance
+/// * `name` - The name of the export
+/// * `params` - The parameters
+/// * `failures` - The failure counter to increment
+fn call_reporting(
+    store: &mut Store<HostState>,
+    instance: &Instance,
+    name: &str,
+    params: &[Val],
+    failures: &mut u32,
+) -> Option<Vec<Val>> {
+    match call_export(store, instance, name, params) {
+        Ok(results) => Some(results),
+        Err(e) => {
+            print_error(&format!("`{name}` trapped: {e:#}"));
+            *failures += 1;
+            None
+        }
+    }
+}
+
+/// Allocates a zeroed sGaugeDrawData struct in the module's memory, if it exports an allocator
+///
+/// * `store` - The store of the instance
+/// * `instance` - The instance
+fn allocate_draw_data(store: &mut Store<HostState>, instance: &Instance) -> Result<Option<i32>> {
+    if instance.get_func(&*store, "malloc").is_none() {
+        return Ok(None);
+    }
+
+    let results = call_export(store, instance, "malloc", &[Val::I32(DRAW_DATA_SIZE)])?;
+    let Some(pointer) = results.first().and_then(Val::i32) else {
+        return Err(anyhow!("malloc returned an unexpected value"));
+    };
+    if pointer == 0 {
+        return Err(anyhow!("malloc failed to allocate the gauge draw data"));
+    }
+
+    Ok(Some(pointer))
+}
+
+/// Fills the sGaugeDrawData struct for a frame: mouse position, time, time delta and window/framebuffer sizes
+///
+/// * `store` - The store of the instance
+/// * `instance` - The instance
+/// * `pointer` - The address of the struct
+/// * `frame` - The frame number
+fn write_draw_data(
+    store: &mut Store<HostState>,
+    instance: &Instance,
+    pointer: i32,
+    frame: u32,
+) -> Result<()> {
+    let delta = 1.0 / FRAME_RATE;
+    let mut data = vec![];
+    for value in [0.0, 0.0, frame as f64 * delta, delta] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    for value in [WINDOW_SIZE.0, WINDOW_SIZE.1, WINDOW_SIZE.0, WINDOW_SIZE.1] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let memory = instance
+        .get_memory(&*store, "memory")
+        .ok_or(anyhow!("module doesn't export its memory"))?;
+    memory
+        .write(&mut *store, pointer as usize, &data)
+        .map_err(|e| anyhow!("{e}"))?;
+
+    Ok(())
+}
+
+/// Builds the parameters of a gauge callback call. The signature is `(FsContext ctx, int service_id, void* data) -> bool`
+///
+/// * `param_types` - The parameter types of the callback
+/// * `service_id` - The service ID to pass
+/// * `data` - The data pointer to pass
+fn gauge_callback_params(param_types: &[ValType], service_id: i32, data: i32) -> Vec<Val> {
+    param_types
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| match (i, ty) {
+            (1, ValType::I32) => Val::I32(service_id),
+            (2, ValType::I32) => Val::I32(data),
+            (_, ValType::F64) => Val::F64(F64::from_float(0.0)),
+            _ => Val::default(*ty),
+        })
+        .collect()
+}
+
+/// Runs a single gauge callback through its lifecycle
+///
+/// * `store` - The store of the instance
+/// * `instance` - The instance
+/// * `callback` - The name of the gauge callback export
+/// * `frames` - How many frames to draw
+/// * `draw_data` - The address of the draw data struct, if one could be allocated
+/// * `failures` - The failure counter to increment
+fn run_gauge(
+    store: &mut Store<HostState>,
+    instance: &Instance,
+    callback: &str,
+    frames: u32,
+    draw_data: Option<i32>,
+    failures: &mut u32,
+) -> Result<()> {
+    let param_types = get_export_func(store, instance, callback)?
+        .ty(&*store)
+        .params()
+        .to_vec();
+
+    let mut call_service = |store: &mut Store<HostState>, service_id: i32, service: &str| {
+        let params = gauge_callback_params(&param_types, service_id, draw_data.unwrap_or(0));
+        let results = call_reporting(store, instance, callback, &params, failures)?;
+        if let Some(Val::I32(0)) = results.first() {
+            print_error(&format!("`{callback}` returned false for {service}"));
+            *failures += 1;
+            return None;
+        }
+        Some(())
+    };
+
+    print_info(&format!("Running gauge `{callback}`"));
+    if call_service(
+        store,
+        PANEL_SERVICE_PRE_INSTALL,
+        "PANEL_SERVICE_PRE_INSTALL",
+    )
+    .is_none()
+        || call_service(
+            store,
+            PANEL_SERVICE_POST_INSTALL,
+            "PANEL_SERVICE_POST_INSTALL",
+        )
+        .is_none()
+    {
+        return Ok(());
+    }
+
+    for frame in 0..frames {
+        if let Some(pointer) = draw_data {
+            write_draw_data(store, instance, pointer, frame)?;
+        }
+        if call_service(store, PANEL_SERVICE_PRE_DRAW, "PANEL_SERVICE_PRE_DRAW").is_none() {
+            return Ok(());
+        }
+    }
+
+    call_service(store, PANEL_SERVICE_PRE_KILL, "PANEL_SERVICE_PRE_KILL");
+
+    Ok(())
+}
+
+/// Loads a module in an embedded runtime and drives it like the sim would: `module_init`, every gauge callback through install, N frames of drawing and kill, then `module_deinit`
+///
+/// Fails if anything trapped or a gauge callback returned false
+///
+/// * `wasm_path` - The path of the WASM module
+/// * `frames` - How many frames to draw
+/// * `stub_values` - Values returned by stubbed imports, keyed by import name
+pub fn smoke_test(wasm_path: &Path, frames: u32, stub_values: HashMap<String, f64>) -> Result<()> {
+    let wasm = fs::read(wasm_path)?;
+    let (mut store, instance) = instantiate(
+        &wasm,
+        RuntimeOptions {
+            args: vec![],
+            stub_values,
+            log_calls: true,
+            capture_output: false,
+        },
+    )?;
+
+    let mut failures = 0;
+
+    // Run static constructors, the sim does this before anything else
+    for constructor in ["_initialize", "__wasm_call_ctors"] {
+        if instance.get_func(&store, constructor).is_some() {
+            call_reporting(&mut store, &instance, constructor, &[], &mut failures);
+            break;
+        }
+    }
+
+    if instance.get_func(&store, "module_init").is_some() {
+        print_info("Running `module_init`");
+        call_reporting(&mut store, &instance, "module_init", &[], &mut failures);
+    }
+
+    let gauge_callbacks = instance
+        .exports(&store)
+        .filter(|e| e.name().ends_with(GAUGE_CALLBACK_SUFFIX) && e.ty(&store).func().is_some())
+        .map(|e| e.name().to_string())
+        .collect::<Vec<_>>();
+    if !gauge_callbacks.is_empty() {
+        let draw_data = allocate_draw_data(&mut store, &instance)?;
+        for callback in &gauge_callbacks {
+            run_gauge(
+                &mut store,
+                &instance,
+                callback,
+                frames,
+                draw_data,
+                &mut failures,
+            )?;
+        }
+    }
+
+    if instance.get_func(&store, "module_deinit").is_some() {
+        print_info("Running `module_deinit`");
+        call_reporting(&mut store, &instance, "module_deinit", &[], &mut failures);
+    }
+
+    for (import, count) in &store.data().call_counts {
+        print_info(&format!("{import} called {count} time(s)"));
+    }
+
+    if failures > 0 {
+        return Err(anyhow!("smoke test failed with {failures} failure(s)"));
+    }
+    print_success(&format!(
+        "Smoke test passed ({} gauge(s), {frames} frame(s))",
+        gauge_callbacks.len()
+    ));
+
+    Ok(())
+}
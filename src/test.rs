@@ -0,0 +1,287 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use cargo_metadata::Message;
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    build::Builder,
+    get_sdk_manager,
+    output::{is_formatted_output, CliBuildObserver, LibtestEvent, SuiteCounts},
+    runtime::{call_export, instantiate, RuntimeOptions},
+    SimulatorVersion,
+};
+
+// libtest's exit code for failed tests
+const FAILURE_EXIT_CODE: i32 = 101;
+
+/// The outcome of a single test
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Ok,
+    Failed,
+    Ignored,
+}
+
+/// The result of a single test
+#[derive(Debug, Serialize)]
+pub struct TestResult {
+    pub name: String,
+    pub status: TestStatus,
+    /// Output of the test, only kept for failed tests
+    pub output: Option<String>,
+}
+
+/// The results of all tests in a test binary
+#[derive(Debug, Serialize)]
+pub struct TestBinaryResult {
+    pub path: PathBuf,
+    pub tests: Vec<TestResult>,
+}
+
+/// The results of a test run
+#[derive(Debug, Serialize)]
+pub struct TestReport {
+    pub binaries: Vec<TestBinaryResult>,
+    pub passed: usize,
+    pub failed: usize,
+    pub ignored: usize,
+}
+
+/// Runs a test binary with the given libtest arguments, returning its exit code and output
+///
+/// * `wasm` - The test binary
+/// * `args` - The libtest arguments
+/// * `stub_values` - Values returned by stubbed imports, keyed by import name
+fn run_test_binary(
+    wasm: &[u8],
+    args: &[&str],
+    stub_values: &HashMap<String, f64>,
+) -> Result<(i32, String)> {
+    let (mut store, instance) = instantiate(
+        wasm,
+        RuntimeOptions {
+            args: ["test"].iter().chain(args).map(|a| a.to_string()).collect(),
+            stub_values: stub_values.clone(),
+            log_calls: false,
+            capture_output: true,
+        },
+    )?;
+
+    let exit_code = match call_export(&mut store, &instance, "_start", &[]) {
+        Ok(_) => 0,
+        Err(e) => match e.downcast_ref::<wasmi::Error>() {
+            Some(error) if error.i32_exit_status().is_some() => error.i32_exit_status().unwrap(),
+            // A panic aborts, which traps. There is no unwinding in WASM
+            _ => {
+                let mut output = String::from_utf8_lossy(&store.data().output).to_string();
+                output.push_str(&format!("{e:#}\n"));
+                return Ok((FAILURE_EXIT_CODE, output));
+            }
+        },
+    };
+
+    Ok((
+        exit_code,
+        String::from_utf8_lossy(&store.data().output).to_string(),
+    ))
+}
+
+/// Lists the tests of a test binary
+///
+/// * `wasm` - The test binary
+/// * `ignored` - Whether to only list ignored tests
+/// * `stub_values` - Values returned by stubbed imports, keyed by import name
+fn list_tests(
+    wasm: &[u8],
+    ignored: bool,
+    stub_values: &HashMap<String, f64>,
+) -> Result<Vec<String>> {
+    let args: &[&str] = if ignored {
+        &["--list", "--ignored"]
+    } else {
+        &["--list"]
+    };
+    let (exit_code, output) = run_test_binary(wasm, args, stub_values)?;
+    if exit_code != 0 {
+        return Err(anyhow!("couldn't list tests:\n{output}"));
+    }
+
+    // Tests are listed as `name: test`
+    Ok(output
+        .lines()
+        .filter_map(|l| l.strip_suffix(": test"))
+        .map(|l| l.to_string())
+        .collect())
+}
+
+/// Runs every test of a test binary, each one in a fresh instance since a panic aborts the whole module. Results are printed in libtest format as tests run, or as libtest's JSON events when formatting output as JSON
+///
+/// * `path` - The path of the test binary
+/// * `stub_values` - Values returned by stubbed imports, keyed by import name
+fn run_tests(path: PathBuf, stub_values: &HashMap<String, f64>) -> Result<TestBinaryResult> {
+    let json = is_formatted_output();
+    let wasm = fs::read(&path)?;
+    let tests = list_tests(&wasm, false, stub_values)?;
+    let ignored = list_tests(&wasm, true, stub_values)?;
+
+    if json {
+        LibtestEvent::Suite {
+            event: "started",
+            counts: SuiteCounts::Started {
+                test_count: tests.len(),
+            },
+        }
+        .emit();
+    } else {
+        println!("\n     Running {}", path.display());
+        println!("\nrunning {} tests", tests.len());
+    }
+
+    let mut results = vec![];
+    for name in tests {
+        if json {
+            LibtestEvent::Test {
+                event: "started",
+                name: &name,
+                stdout: None,
+            }
+            .emit();
+        }
+
+        let (status, output) = if ignored.contains(&name) {
+            (TestStatus::Ignored, None)
+        } else {
+            let (exit_code, output) =
+                run_test_binary(&wasm, &[&name, "--exact", "--nocapture"], stub_values)?;
+            if exit_code == 0 {
+                (TestStatus::Ok, None)
+            } else {
+                (TestStatus::Failed, Some(output))
+            }
+        };
+
+        if json {
+            LibtestEvent::Test {
+                event: match status {
+                    TestStatus::Ok => "ok",
+                    TestStatus::Failed => "failed",
+                    TestStatus::Ignored => "ignored",
+                },
+                name: &name,
+                stdout: output.as_deref(),
+            }
+            .emit();
+        } else {
+            let status = match status {
+                TestStatus::Ok => style("ok").green(),
+                TestStatus::Failed => style("FAILED").red(),
+                TestStatus::Ignored => style("ignored").yellow(),
+            };
+            println!("test {name} ... {status}");
+        }
+        results.push(TestResult {
+            name,
+            status,
+            output,
+        });
+    }
+
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    if json {
+        LibtestEvent::Suite {
+            event: if count(TestStatus::Failed) == 0 {
+                "ok"
+            } else {
+                "failed"
+            },
+            counts: SuiteCounts::Finished {
+                passed: count(TestStatus::Ok),
+                failed: count(TestStatus::Failed),
+                ignored: count(TestStatus::Ignored),
+                measured: 0,
+                filtered_out: 0,
+            },
+        }
+        .emit();
+    } else {
+        let failed = results
+            .iter()
+            .filter(|r| r.status == TestStatus::Failed)
+            .collect::<Vec<_>>();
+        if !failed.is_empty() {
+            println!("\nfailures:");
+            for result in &failed {
+                println!("\n---- {} stdout ----", result.name);
+                print!("{}", result.output.as_deref().unwrap_or_default());
+            }
+        }
+
+        println!(
+            "\ntest result: {}. {} passed; {} failed; {} ignored\n",
+            if failed.is_empty() {
+                style("ok").green()
+            } else {
+                style("FAILED").red()
+            },
+            count(TestStatus::Ok),
+            count(TestStatus::Failed),
+            count(TestStatus::Ignored),
+        );
+    }
+
+    Ok(TestBinaryResult {
+        path,
+        tests: results,
+    })
+}
+
+/// Compiles the tests of a crate for the given simulator and runs them in an embedded WASI runtime with stubbed sim functions
+///
+/// * `sim_version` - The simulator version to compile for
+/// * `in_folder` - The path to the crate
+/// * `stub_values` - Values returned by stubbed imports, keyed by import name
+pub fn test_crate(
+    sim_version: SimulatorVersion,
+    in_folder: &str,
+    stub_values: HashMap<String, f64>,
+) -> Result<TestReport> {
+    let messages = Builder::new(get_sdk_manager()?).run_cargo(
+        sim_version,
+        in_folder,
+        &["test", "--no-run"],
+        false,
+        &mut CliBuildObserver,
+    )?;
+
+    let executables = messages
+        .iter()
+        .filter_map(|m| match m {
+            Message::CompilerArtifact(artifact) if artifact.profile.test => {
+                artifact.executable.clone()
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut binaries = vec![];
+    for executable in executables {
+        binaries.push(run_tests(executable.into_std_path_buf(), &stub_values)?);
+    }
+
+    let count = |status| {
+        binaries
+            .iter()
+            .flat_map(|b| &b.tests)
+            .filter(|t| t.status == status)
+            .count()
+    };
+    Ok(TestReport {
+        passed: count(TestStatus::Ok),
+        failed: count(TestStatus::Failed),
+        ignored: count(TestStatus::Ignored),
+        binaries,
+    })
+}
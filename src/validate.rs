@@ -0,0 +1,106 @@
+use anyhow::Result;
+
+use crate::{
+    analysis::{ModuleAnalysis, WasmFeature},
+    build::BuildError,
+    SimulatorVersion,
+};
+
+// How many offending functions are listed per feature
+const MAX_LISTED_FUNCTIONS: usize = 10;
+
+// The WASM features each sim fails to load modules with
+const MSFS2020_DISALLOWED_FEATURES: &[WasmFeature] = &[
+    WasmFeature::SignExt,
+    WasmFeature::MultiValue,
+    WasmFeature::ReferenceTypes,
+    WasmFeature::Simd,
+    WasmFeature::Threads,
+    WasmFeature::TailCall,
+    WasmFeature::Exceptions,
+    WasmFeature::Gc,
+    WasmFeature::Other,
+];
+const MSFS2024_DISALLOWED_FEATURES: &[WasmFeature] = &[
+    WasmFeature::SignExt,
+    WasmFeature::MultiValue,
+    WasmFeature::ReferenceTypes,
+    WasmFeature::Simd,
+    WasmFeature::Threads,
+    WasmFeature::TailCall,
+    WasmFeature::Exceptions,
+    WasmFeature::Gc,
+    WasmFeature::Other,
+];
+
+/// Gets the WASM features a simulator fails to load modules with
+///
+/// * `sim_version` - The simulator version
+pub fn disallowed_features(sim_version: SimulatorVersion) -> &'static [WasmFeature] {
+    match sim_version {
+        SimulatorVersion::Msfs2020 => MSFS2020_DISALLOWED_FEATURES,
+        SimulatorVersion::Msfs2024 => MSFS2024_DISALLOWED_FEATURES,
+    }
+}
+
+/// Describes the use of a feature in a module, listing the functions using it. None if the module doesn't use it
+///
+/// Example: `sign-ext is used by 2 function(s): foo, bar`
+///
+/// * `analysis` - The analysis of the module
+/// * `feature` - The feature
+pub fn describe_feature_use(analysis: &ModuleAnalysis, feature: WasmFeature) -> Option<String> {
+    let functions = analysis.features.get(&feature)?;
+    if functions.is_empty() {
+        return Some(format!("{} is used by the module", feature.name()));
+    }
+
+    let mut listed = functions
+        .iter()
+        .take(MAX_LISTED_FUNCTIONS)
+        .cloned()
+        .collect::<Vec<_>>();
+    if functions.len() > MAX_LISTED_FUNCTIONS {
+        listed.push(format!(
+            "and {} more",
+            functions.len() - MAX_LISTED_FUNCTIONS
+        ));
+    }
+    Some(format!(
+        "{} is used by {} function(s): {}",
+        feature.name(),
+        functions.len(),
+        listed.join(", ")
+    ))
+}
+
+/// Describes every use of a feature the simulator doesn't support, listing the offending functions
+///
+/// * `analysis` - The analysis of the module
+/// * `sim_version` - The simulator version the module is for
+pub fn find_disallowed_features(
+    analysis: &ModuleAnalysis,
+    sim_version: SimulatorVersion,
+) -> Vec<String> {
+    disallowed_features(sim_version)
+        .iter()
+        .filter_map(|feature| describe_feature_use(analysis, *feature))
+        .collect()
+}
+
+/// Fails if a module uses WASM features the simulator doesn't support, since the sim silently fails to load such modules
+///
+/// * `analysis` - The analysis of the module
+/// * `sim_version` - The simulator version the module is for
+pub fn validate_module(analysis: &ModuleAnalysis, sim_version: SimulatorVersion) -> Result<()> {
+    let violations = find_disallowed_features(analysis, sim_version);
+    if !violations.is_empty() {
+        return Err(BuildError::Validation(format!(
+            "module uses WASM features {sim_version:?} doesn't support:\n  {}",
+            violations.join("\n  ")
+        ))
+        .into());
+    }
+
+    Ok(())
+}
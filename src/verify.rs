@@ -0,0 +1,67 @@
+use std::{env, fs, path::Path};
+
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    build::{BuildOutput, BuildPlan, Builder},
+    get_sdk_manager,
+    output::CliBuildObserver,
+    print_info, print_success, print_warning,
+    provenance::read_build_info,
+    SimulatorVersion,
+};
+
+/// Hashes a file with SHA-256
+///
+/// * `path` - The path of the file
+fn hash_file(path: &Path) -> Result<String> {
+    Ok(format!("{:x}", Sha256::digest(fs::read(path)?)))
+}
+
+/// Rebuilds a crate reproducibly and checks the result is byte for byte identical to a given artifact
+///
+/// * `sim_version` - The simulator version the artifact was built for
+/// * `in_folder` - The path to the crate
+/// * `artifact` - The previously built module to compare against
+pub fn verify_build(sim_version: SimulatorVersion, in_folder: &str, artifact: &Path) -> Result<()> {
+    let expected = hash_file(artifact)?;
+
+    // Point out the most likely reason for a mismatch up front
+    if let Some(build_info) = read_build_info(&fs::read(artifact)?)? {
+        if build_info.git_dirty == Some(true) {
+            print_warning("the artifact was built from a working tree with uncommitted changes");
+        }
+        if let Some(commit) = build_info.git_commit {
+            print_info(&format!("Artifact was built from commit {commit}"));
+        }
+    }
+
+    let rebuilt = env::temp_dir().join(format!("cargo-msfs-verify-{}.wasm", std::process::id()));
+    let plan = BuildPlan::new(
+        sim_version,
+        in_folder,
+        BuildOutput::Wasm(
+            rebuilt
+                .to_str()
+                .ok_or(anyhow!("couldn't convert to str"))?
+                .to_string(),
+        ),
+    )
+    .reproducible(true)
+    .save_report(false);
+    let result = Builder::new(get_sdk_manager()?)
+        .build(&plan, &mut CliBuildObserver)
+        .and_then(|_| hash_file(&rebuilt));
+    let _ = fs::remove_file(&rebuilt);
+    let actual = result?;
+
+    print_info(&format!("Artifact: {expected}"));
+    print_info(&format!("Rebuilt:  {actual}"));
+    if expected != actual {
+        return Err(anyhow!("rebuilt module doesn't match the artifact"));
+    }
+    print_success("Rebuilt module is identical to the artifact");
+
+    Ok(())
+}
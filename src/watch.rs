@@ -0,0 +1,243 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use cargo_metadata::MetadataCommand;
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode};
+
+use crate::{
+    build::{BuildOutput, BuildPlan, Builder},
+    output::{print_build_result, CliBuildObserver},
+    print_error, print_info, print_success,
+};
+
+// How long to wait for file changes to settle before rebuilding
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(500);
+
+/// The paths to watch for a crate, along with the paths whose changes should be ignored
+struct WatchPaths {
+    /// Source directories and manifests of every local package in the build
+    watched: BTreeSet<PathBuf>,
+    /// Directories that are written to by the build itself
+    ignored: Vec<PathBuf>,
+}
+
+/// Resolves a path to an absolute path without symlinks like canonicalize does, even if it doesn't exist (anymore). Only the part of the path that exists is resolved
+///
+/// * `path` - The path to normalize
+fn normalize_path(path: &Path) -> PathBuf {
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+
+    for ancestor in absolute.ancestors() {
+        if let Ok(canonical) = ancestor.canonicalize() {
+            return match absolute.strip_prefix(ancestor) {
+                Ok(rest) if !rest.as_os_str().is_empty() => canonical.join(rest),
+                _ => canonical,
+            };
+        }
+    }
+
+    absolute
+}
+
+/// Checks whether a changed path is inside one of the ignored paths
+///
+/// * `path` - The changed path as reported by the watcher, which doesn't resolve symlinks
+/// * `ignored` - The normalized paths to ignore
+fn is_ignored(path: &Path, ignored: &[PathBuf]) -> bool {
+    let path = normalize_path(path);
+    ignored.iter().any(|i| path.starts_with(i))
+}
+
+/// Collects the source directories of the crate and its path dependencies
+///
+/// Local packages (workspace members and path dependencies) are the ones without a registry or git source
+///
+/// * `in_folder` - The path to the crate to build
+fn get_watch_paths(in_folder: &str) -> Result<WatchPaths> {
+    let metadata = MetadataCommand::new()
+        .current_dir(in_folder.replace("\\", "/"))
+        .exec()?;
+
+    let mut watched = BTreeSet::new();
+    for package in metadata.packages.iter().filter(|p| p.source.is_none()) {
+        watched.insert(package.manifest_path.clone().into_std_path_buf());
+        for target in &package.targets {
+            // Build scripts live in the package root, which would also contain the target directory
+            if target.kind.iter().any(|k| k == "custom-build") {
+                watched.insert(target.src_path.clone().into_std_path_buf());
+                continue;
+            }
+
+            let source_directory = target
+                .src_path
+                .parent()
+                .context("couldn't get target source directory")?;
+            watched.insert(source_directory.to_path_buf().into_std_path_buf());
+        }
+    }
+
+    // Nested source files (e.g. src/bin/foo.rs) are covered by the recursive watch of their parent
+    let mut deduplicated = BTreeSet::new();
+    for path in &watched {
+        if !watched
+            .iter()
+            .any(|other| other != path && other.is_dir() && path.starts_with(other))
+        {
+            deduplicated.insert(path.clone());
+        }
+    }
+
+    Ok(WatchPaths {
+        watched: deduplicated,
+        ignored: vec![normalize_path(metadata.target_directory.as_std_path())],
+    })
+}
+
+/// Runs a build, reporting the result without returning errors so the watch loop keeps going
+///
+/// * `builder` - The builder to build with
+/// * `plan` - What to build and where to write it
+fn run_build(builder: &Builder, plan: &BuildPlan) {
+    print_info("Building...");
+    match builder.build(plan, &mut CliBuildObserver) {
+        Ok(result) => {
+            print_build_result(&result);
+            print_success(&format!(
+                "Build finished, wrote {}",
+                result.module_path().display()
+            ));
+        }
+        Err(e) => print_error(&format!("{e:#}")),
+    }
+}
+
+/// Builds the crate and rebuilds it every time one of its sources changes. This never returns unless watching fails
+///
+/// * `builder` - The builder to build with
+/// * `plan` - What to build and where to write it
+pub fn watch_crate(builder: &Builder, plan: &BuildPlan) -> Result<()> {
+    let mut paths = get_watch_paths(&plan.in_folder)?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut debouncer = new_debouncer(DEBOUNCE_DURATION, sender)?;
+    for path in &paths.watched {
+        debouncer
+            .watcher()
+            .watch(path, RecursiveMode::Recursive)
+            .with_context(|| format!("couldn't watch {}", path.display()))?;
+    }
+
+    // The output may be written inside one of the watched directories, so make sure we don't trigger on our own writes.
+    // When building into a package, layout.json is rewritten as well so the whole package is ignored
+    match &plan.output {
+        BuildOutput::Wasm(out_wasm) => {
+            let out_path = PathBuf::from(out_wasm.replace("\\", "/"));
+            paths.ignored.push(normalize_path(&out_path));
+        }
+        BuildOutput::Package(package_folder) => {
+            fs::create_dir_all(package_folder)?;
+            paths.ignored.push(normalize_path(package_folder));
+        }
+    }
+    run_build(builder, plan);
+    print_info("Watching for changes...");
+
+    for result in receiver {
+        let events = match result {
+            Ok(events) => events,
+            Err(e) => {
+                print_error(&format!("watch error: {e}"));
+                continue;
+            }
+        };
+
+        if let Some(event) = events.iter().find(|e| !is_ignored(&e.path, &paths.ignored)) {
+            print_info(&format!("Change detected in {}", event.path.display()));
+            run_build(builder, plan);
+            print_info("Watching for changes...");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh temporary directory, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("cargo-msfs-watch-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn ignores_paths_that_dont_exist_yet() {
+        let dir = TempDir::new("missing");
+        // The output of a build that failed before writing it
+        let out_path = dir.0.join("out/gauge.wasm");
+        let ignored = vec![normalize_path(&out_path)];
+
+        fs::create_dir_all(dir.0.join("out")).unwrap();
+        fs::write(&out_path, "wasm").unwrap();
+        assert!(is_ignored(&out_path, &ignored));
+        assert!(is_ignored(&out_path.canonicalize().unwrap(), &ignored));
+        assert!(!is_ignored(&dir.0.join("out/other.wasm"), &ignored));
+
+        // Removed files are reported too
+        fs::remove_file(&out_path).unwrap();
+        assert!(is_ignored(&out_path, &ignored));
+    }
+
+    #[test]
+    fn ignores_relative_paths() {
+        let target = std::env::current_dir().unwrap().join("target");
+        let ignored = vec![normalize_path(Path::new("target"))];
+        assert!(is_ignored(&target.join("debug/build"), &ignored));
+        assert!(!is_ignored(Path::new("src/lib.rs"), &ignored));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ignores_paths_through_symlinks() {
+        let dir = TempDir::new("symlink");
+        let real = dir.0.join("real");
+        let link = dir.0.join("link");
+        fs::create_dir_all(real.join("target/wasm32-wasip1")).unwrap();
+        fs::create_dir_all(real.join("src")).unwrap();
+        fs::create_dir_all(real.join("target-other")).unwrap();
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        // Events can be reported through either path, whichever was ignored
+        for (ignored_root, event_root) in [(&link, &real), (&real, &link)] {
+            let ignored = vec![normalize_path(&ignored_root.join("target"))];
+            assert!(is_ignored(
+                &event_root.join("target/wasm32-wasip1/release/gauge.wasm"),
+                &ignored
+            ));
+            assert!(!is_ignored(&event_root.join("src/lib.rs"), &ignored));
+            assert!(!is_ignored(&event_root.join("target-other/file"), &ignored));
+        }
+    }
+}
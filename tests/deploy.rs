@@ -0,0 +1,140 @@
+//! Deploy and undeploy tests against a temporary Community folder
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// A package and a Community folder in a fresh temporary directory, removed when dropped
+struct Fixture {
+    root: PathBuf,
+    package: PathBuf,
+    community: PathBuf,
+}
+
+impl Fixture {
+    fn new(name: &str) -> Self {
+        let root =
+            std::env::temp_dir().join(format!("cargo-msfs-deploy-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let package = root.join("my-package");
+        let community = root.join("Community");
+        fs::create_dir_all(package.join("SimObjects")).unwrap();
+        fs::write(package.join("manifest.json"), "{}").unwrap();
+        fs::write(package.join("SimObjects/module.wasm"), "wasm").unwrap();
+        fs::create_dir_all(&community).unwrap();
+
+        Self {
+            root,
+            package,
+            community,
+        }
+    }
+
+    fn deployed(&self) -> PathBuf {
+        self.community.join("my-package")
+    }
+
+    fn run(&self, command: &str, extra: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_cargo-msfs"))
+            .arg(command)
+            .arg("-p")
+            .arg(&self.package)
+            .arg("-c")
+            .arg(&self.community)
+            .args(extra)
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn is_link(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.is_symlink())
+}
+
+#[test]
+fn link() {
+    let fixture = Fixture::new("link");
+    assert_success(&fixture.run("deploy", &[]));
+    // Windows falls back to copying without symlink privileges
+    if cfg!(unix) {
+        assert!(is_link(&fixture.deployed()));
+    }
+    assert!(fixture.deployed().join("SimObjects/module.wasm").is_file());
+
+    assert_success(&fixture.run("undeploy", &[]));
+    assert!(fs::symlink_metadata(fixture.deployed()).is_err());
+    // The package itself is left alone
+    assert!(fixture.package.join("SimObjects/module.wasm").is_file());
+}
+
+#[test]
+fn copy() {
+    let fixture = Fixture::new("copy");
+    assert_success(&fixture.run("deploy", &["--copy"]));
+    assert!(!is_link(&fixture.deployed()));
+    assert_eq!(
+        fs::read_to_string(fixture.deployed().join("SimObjects/module.wasm")).unwrap(),
+        "wasm"
+    );
+
+    assert_success(&fixture.run("undeploy", &[]));
+    assert!(!fixture.deployed().exists());
+    assert!(fixture.package.join("SimObjects/module.wasm").is_file());
+}
+
+#[test]
+fn redeploy() {
+    let fixture = Fixture::new("redeploy");
+    assert_success(&fixture.run("deploy", &["--copy"]));
+    fs::write(fixture.package.join("SimObjects/module.wasm"), "rebuilt").unwrap();
+    assert_success(&fixture.run("deploy", &["--copy"]));
+    assert_eq!(
+        fs::read_to_string(fixture.deployed().join("SimObjects/module.wasm")).unwrap(),
+        "rebuilt"
+    );
+
+    // Switching from a copy to a link replaces the copy
+    assert_success(&fixture.run("deploy", &[]));
+    if cfg!(unix) {
+        assert!(is_link(&fixture.deployed()));
+    }
+}
+
+#[test]
+fn foreign_folder() {
+    let fixture = Fixture::new("foreign");
+    let foreign_file = fixture.deployed().join("foreign.txt");
+    fs::create_dir_all(fixture.deployed()).unwrap();
+    fs::write(&foreign_file, "not ours").unwrap();
+
+    assert!(!fixture.run("deploy", &[]).status.success());
+    assert!(!fixture.run("deploy", &["--copy"]).status.success());
+    assert!(!fixture.run("undeploy", &[]).status.success());
+    assert_eq!(fs::read_to_string(&foreign_file).unwrap(), "not ours");
+}
+
+#[test]
+fn stale_deployment() {
+    let fixture = Fixture::new("stale");
+    assert_success(&fixture.run("deploy", &[]));
+    fs::remove_dir_all(&fixture.package).unwrap();
+
+    assert_success(&fixture.run("undeploy", &[]));
+    assert!(fs::symlink_metadata(fixture.deployed()).is_err());
+}
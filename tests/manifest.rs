@@ -0,0 +1,172 @@
+//! Manifest parsing tests
+//!
+//! The fixtures are hand-written in the layout of the published `sdk.json` files, not saved copies of them. Saved copies can replace them as long as the expected versions below are updated
+
+use cargo_msfs::{
+    sdk::{
+        get_release_number, is_same_sdk_version, parse_sdk_version, SdkManifest, CORE_INSTALLER_KEY,
+    },
+    SdkError,
+};
+
+const MSFS2020_MANIFEST: &str = include_str!("fixtures/msfs2020_sdk.json");
+const MSFS2024_MANIFEST: &str = include_str!("fixtures/msfs2024_sdk.json");
+
+fn latest_version(manifest: &SdkManifest) -> String {
+    get_release_number(manifest.latest_release().unwrap()).unwrap()
+}
+
+#[test]
+fn msfs2020_latest_release() {
+    let manifest = SdkManifest::parse(MSFS2020_MANIFEST).unwrap();
+    assert_eq!(latest_version(&manifest), "0.24.3");
+}
+
+#[test]
+fn msfs2024_latest_release() {
+    let manifest = SdkManifest::parse(MSFS2024_MANIFEST).unwrap();
+    assert_eq!(latest_version(&manifest), "1.2.9");
+}
+
+#[test]
+fn latest_release_ignores_order() {
+    for contents in [MSFS2020_MANIFEST, MSFS2024_MANIFEST] {
+        let mut manifest = SdkManifest::parse(contents).unwrap();
+        let expected = latest_version(&manifest);
+
+        manifest.game_versions.reverse();
+        for release in &mut manifest.game_versions {
+            release.release_notes.reverse();
+        }
+        assert_eq!(latest_version(&manifest), expected);
+    }
+}
+
+#[test]
+fn versions_compare_numerically() {
+    // 1.1.10 is newer than 1.1.9, even though it sorts before it as a string
+    let manifest = SdkManifest::parse(MSFS2024_MANIFEST).unwrap();
+    let release = manifest.find_release("1.1.10").unwrap();
+    assert_eq!(get_release_number(release).unwrap(), "1.1.10");
+
+    assert!(parse_sdk_version("0.24") < parse_sdk_version("0.24.1"));
+    assert_eq!(parse_sdk_version("0.24"), parse_sdk_version("0.24.0"));
+    assert_eq!(parse_sdk_version("not a version"), None);
+}
+
+#[test]
+fn four_part_versions() {
+    // Installers are versioned with a fourth revision component
+    assert_eq!(parse_sdk_version("0.24.3.0"), parse_sdk_version("0.24.3"));
+    assert!(parse_sdk_version("0.24.3.1") > parse_sdk_version("0.24.3"));
+    assert!(parse_sdk_version("0.24.3.1") < parse_sdk_version("0.24.4"));
+    assert_eq!(parse_sdk_version("0.24.3.0.1"), None);
+
+    let manifest = SdkManifest::parse(
+        r#"{"game_versions": [{
+            "downloads_menu": {"SDK Installer (Core)": {"value": "core.msi"}},
+            "release_notes": ["1.0.0.0", "0.9"]
+        }]}"#,
+    )
+    .unwrap();
+    assert!(manifest.skipped.is_empty());
+    assert!(manifest.find_release("1.0.0").is_ok());
+}
+
+#[test]
+fn release_numbers_are_kept_as_written() {
+    let manifest = SdkManifest::parse(
+        r#"{"game_versions": [{
+            "downloads_menu": {"SDK Installer (Core)": {"value": "core.msi"}},
+            "release_notes": ["0.23", "0.24"]
+        }]}"#,
+    )
+    .unwrap();
+
+    // The version file of installed SDKs holds this, so it must not change with how versions are parsed
+    assert_eq!(latest_version(&manifest), "0.24");
+    assert!(is_same_sdk_version("0.24", "0.24.0"));
+    assert!(is_same_sdk_version("0.24.0.0", "0.24"));
+    assert!(!is_same_sdk_version("0.24", "0.24.1"));
+}
+
+#[test]
+fn find_release() {
+    let manifest = SdkManifest::parse(MSFS2020_MANIFEST).unwrap();
+    let release = manifest.find_release("0.23.1").unwrap();
+    assert_eq!(
+        release.downloads_menu[CORE_INSTALLER_KEY].value.as_deref(),
+        Some("MSFS_SDK_Core_Installer_0.23.1.0.msi")
+    );
+
+    // Only the version a release installs identifies it
+    assert!(matches!(
+        manifest.find_release("0.23.0"),
+        Err(SdkError::ReleaseNotFound(_))
+    ));
+}
+
+/// Parses a manifest that's expected to be invalid, returning the error message
+fn invalid(contents: &str) -> String {
+    match SdkManifest::parse(contents) {
+        Err(SdkError::InvalidManifest(message)) => message,
+        other => panic!("expected an invalid manifest, got {other:?}"),
+    }
+}
+
+#[test]
+fn missing_fields() {
+    assert!(invalid("{}").contains("game_versions"));
+    assert!(invalid(r#"{"game_versions": []}"#).contains("no game versions"));
+    assert!(
+        invalid(r#"{"game_versions": [{"release_notes": ["1.0.0"]}]}"#).contains("downloads_menu")
+    );
+    assert!(invalid(r#"{"game_versions": [{"downloads_menu": {}}]}"#).contains("release_notes"));
+}
+
+#[test]
+fn invalid_release() {
+    let message = invalid(
+        r#"{"game_versions": [{
+            "downloads_menu": {"SDK Installer (Core)": {"value": "core.msi"}},
+            "release_notes": ["soon"]
+        }]}"#,
+    );
+    assert!(message.contains("game version 0"), "{message}");
+    assert!(message.contains("no valid SDK version"), "{message}");
+
+    let message = invalid(
+        r#"{"game_versions": [{
+            "downloads_menu": {"SDK Installer (Core)": {"value": null}},
+            "release_notes": ["1.0.0"]
+        }]}"#,
+    );
+    assert!(message.contains("SDK 1.0.0"), "{message}");
+    assert!(message.contains(CORE_INSTALLER_KEY), "{message}");
+}
+
+#[test]
+fn skips_invalid_releases() {
+    let manifest = SdkManifest::parse(
+        r#"{"game_versions": [
+            {"release_notes": ["2.0.0"]},
+            {
+                "downloads_menu": {"SDK Installer (Core)": {"value": "core.msi"}},
+                "release_notes": ["1.0.0"]
+            },
+            {
+                "downloads_menu": {"SDK Installer (Core)": {"value": ""}},
+                "release_notes": ["3.0.0"]
+            }
+        ]}"#,
+    )
+    .unwrap();
+
+    assert_eq!(manifest.game_versions.len(), 1);
+    assert_eq!(latest_version(&manifest), "1.0.0");
+    assert_eq!(manifest.skipped.len(), 2);
+    assert!(manifest.skipped[0].starts_with("game version 0"));
+    assert!(manifest.skipped[0].contains("downloads_menu"));
+    assert!(manifest.skipped[1].starts_with("game version 2"));
+    assert!(manifest.skipped[1].contains(CORE_INSTALLER_KEY));
+}
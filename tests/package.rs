@@ -0,0 +1,220 @@
+//! Package tests generating layout.json and manifest.json for a temporary package folder
+
+use std::{
+    fs::{self, File},
+    path::PathBuf,
+    process::{Command, Output},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+// 2020-09-13T12:26:40.1234567Z, and the same time as a Windows FILETIME
+const MODIFIED_UNIX_NANOS: u64 = 1_600_000_000_123_456_700;
+const MODIFIED_FILETIME: u64 = 132_444_736_001_234_567;
+
+/// A crate and a package folder in a fresh temporary directory, removed when dropped
+struct Fixture {
+    root: PathBuf,
+    crate_folder: PathBuf,
+    package: PathBuf,
+}
+
+impl Fixture {
+    fn new(name: &str, metadata: &str) -> Self {
+        let root =
+            std::env::temp_dir().join(format!("cargo-msfs-package-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let crate_folder = root.join("my-crate");
+        let package = root.join("my-package");
+        fs::create_dir_all(crate_folder.join("src")).unwrap();
+        fs::write(
+            crate_folder.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"my-crate\"\nversion = \"1.2.3\"\nedition = \"2021\"\n{metadata}"
+            ),
+        )
+        .unwrap();
+        fs::write(crate_folder.join("src/lib.rs"), "").unwrap();
+        fs::create_dir_all(&package).unwrap();
+
+        Self {
+            root,
+            crate_folder,
+            package,
+        }
+    }
+
+    /// Writes a file into the package with a fixed modification time
+    fn write(&self, path: &str, contents: &str) {
+        let path = self.package.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_nanos(MODIFIED_UNIX_NANOS))
+            .unwrap();
+    }
+
+    fn read_json(&self, path: &str) -> Value {
+        serde_json::from_str(&fs::read_to_string(self.package.join(path)).unwrap()).unwrap()
+    }
+
+    fn run(&self) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_cargo-msfs"))
+            .arg("package")
+            .arg("-i")
+            .arg(&self.crate_folder)
+            .arg("-p")
+            .arg(&self.package)
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn assert_failure(output: &Output, message: &str) {
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(message), "{stderr}");
+}
+
+#[test]
+fn layout() {
+    let fixture = Fixture::new("layout", "authors = [\"Jane Doe <jane@example.com>\"]\n");
+    fixture.write(
+        "SimObjects/Airplanes/MyPlane/panel/gauge.wasm",
+        "wasm module",
+    );
+    fixture.write("ModelBehaviorDefs/my-plane.xml", "<ModelBehaviors/>");
+    assert_success(&fixture.run());
+
+    // Entries are sorted, use forward slashes and leave out layout.json and manifest.json
+    assert_eq!(
+        fixture.read_json("layout.json"),
+        json!({
+            "content": [
+                {
+                    "path": "ModelBehaviorDefs/my-plane.xml",
+                    "size": 17,
+                    "date": MODIFIED_FILETIME,
+                },
+                {
+                    "path": "SimObjects/Airplanes/MyPlane/panel/gauge.wasm",
+                    "size": 11,
+                    "date": MODIFIED_FILETIME,
+                },
+            ]
+        })
+    );
+    assert_eq!(
+        fixture.read_json("manifest.json")["total_package_size"],
+        "00000000000000000028"
+    );
+
+    // Packaging again lists the same files, not the generated ones
+    fixture.write("SimObjects/Airplanes/MyPlane/panel/gauge.wasm", "rebuilt");
+    assert_success(&fixture.run());
+    let layout = fixture.read_json("layout.json");
+    let content = layout["content"].as_array().unwrap();
+    assert_eq!(content.len(), 2);
+    assert_eq!(content[1]["size"], 7);
+    assert_eq!(
+        fixture.read_json("manifest.json")["total_package_size"],
+        "00000000000000000024"
+    );
+}
+
+#[test]
+fn new_manifest() {
+    let fixture = Fixture::new(
+        "new-manifest",
+        "authors = [\"Jane Doe <jane@example.com>\"]\n\n[package.metadata.msfs]\ntitle = \"My Plane\"\ncontent-type = \"AIRCRAFT\"\n",
+    );
+    fixture.write("SimObjects/module.wasm", "wasm");
+    assert_success(&fixture.run());
+
+    let manifest = fixture.read_json("manifest.json");
+    assert_eq!(manifest["title"], "My Plane");
+    assert_eq!(manifest["creator"], "Jane Doe");
+    assert_eq!(manifest["package_version"], "1.2.3");
+    assert_eq!(manifest["minimum_game_version"], "1.0.0");
+    assert_eq!(manifest["content_type"], "AIRCRAFT");
+    assert_eq!(manifest["dependencies"], json!([]));
+    assert_eq!(manifest["total_package_size"], "00000000000000000004");
+}
+
+#[test]
+fn missing_creator() {
+    let fixture = Fixture::new("missing-creator", "");
+    assert_failure(&fixture.run(), "`creator` is missing or empty");
+    assert!(!fixture.package.join("manifest.json").exists());
+}
+
+#[test]
+fn existing_manifest() {
+    let fixture = Fixture::new(
+        "existing-manifest",
+        "\n[package.metadata.msfs]\ncreator = \"Jane Doe\"\n",
+    );
+    // Values that aren't declared in Cargo metadata can be edited by hand
+    let manifest = json!({
+        "title": "Hand Edited",
+        "creator": "Jane Doe",
+        "package_version": "1.2.3",
+        "minimum_game_version": "1.30.0",
+        "content_type": "MISC",
+        "manufacturer": "Someone",
+    });
+    fs::write(fixture.package.join("manifest.json"), manifest.to_string()).unwrap();
+    fixture.write("module.wasm", "wasm");
+    assert_success(&fixture.run());
+
+    let mut expected = manifest;
+    expected["total_package_size"] = json!("00000000000000000004");
+    assert_eq!(fixture.read_json("manifest.json"), expected);
+}
+
+#[test]
+fn invalid_manifest() {
+    let fixture = Fixture::new(
+        "invalid-manifest",
+        "\n[package.metadata.msfs]\ncreator = \"Jane Doe\"\n",
+    );
+    let manifest = json!({
+        "title": "my-crate",
+        "creator": "John Doe",
+        "package_version": "1.0.0",
+        "minimum_game_version": "",
+    });
+    fs::write(fixture.package.join("manifest.json"), manifest.to_string()).unwrap();
+
+    let output = fixture.run();
+    assert_failure(&output, "`minimum_game_version` is missing or empty");
+    assert_failure(&output, "`content_type` is missing or empty");
+    assert_failure(
+        &output,
+        "`creator` is `John Doe` but Cargo metadata declares `Jane Doe`",
+    );
+    assert_failure(
+        &output,
+        "`package_version` is `1.0.0` but Cargo metadata declares `1.2.3`",
+    );
+    // The manifest is left as it was
+    assert_eq!(fixture.read_json("manifest.json"), manifest);
+}